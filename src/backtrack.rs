@@ -0,0 +1,157 @@
+use std::sync::Arc;
+
+use crate::{Data, Geometry, Index, Value, SudokuError};
+
+// The digits used so far in each unit, one bit per digit, with units numbered as in
+// `Geometry::units`.  Each cell belongs to one row, one column and one subsquare unit.
+// Related cells, such as a knight's move apart, are checked directly against the grid, and so
+// is every constraint on a cell, such as a killer cage or a thermometer.
+struct Search {
+    geometry: Arc<Geometry>,
+    data: Data,
+    used: Vec<u32>,
+    all_values: u32,
+    limit: usize,
+    count: usize,
+    first: Option<Data>
+}
+
+impl Search {
+    fn new(data: &Data, limit: usize) -> Option<Search> {
+        let geometry = Arc::clone(data.geometry());
+        let mut used = vec!(0u32; geometry.units().count());
+
+        for (idx, item) in data.iter().enumerate() {
+            if let Some(value) = item {
+                if !geometry.values().contains(value) || geometry.related(idx).iter().any(|&other| data[other] == *item) {
+                    return None
+                }
+                let bit = 1 << value;
+                for &unit in geometry.cell_units(idx) {
+                    if used[unit] & bit != 0 {
+                        return None
+                    }
+                    used[unit] |= bit;
+                }
+            }
+        }
+        if !geometry.constraints().iter().all(|constraint| constraint.is_consistent(data)) {
+            return None
+        }
+
+        let all_values = geometry.values().fold(0, |acc, value| acc | 1 << value);
+        Some(Search {geometry, data: data.clone(), used, all_values, limit, count: 0, first: None})
+    }
+
+    fn candidates(&self, idx: Index) -> u32 {
+        let taken = self.geometry.cell_units(idx).iter().fold(0, |acc, &unit| acc | self.used[unit]);
+        let taken = self.geometry.related(idx).iter()
+            .filter_map(|&other| self.data[other])
+            .fold(taken, |acc, value| acc | 1 << value);
+        let mut candidates = self.all_values & !taken;
+
+        for &n in self.geometry.cell_constraints(idx) {
+            let constraint = &self.geometry.constraints()[n];
+            candidates = self.geometry.values()
+                .filter(|&value| candidates & (1 << value) != 0 && constraint.allows(&self.data, idx, value))
+                .fold(0, |acc, value| acc | 1 << value);
+        }
+        candidates
+    }
+
+    // Picks the empty cell with the fewest candidates, returning None when the grid is full.
+    fn next_cell(&self) -> Option<(Index, u32)> {
+        let mut best: Option<(Index, u32)> = None;
+
+        for idx in (0..self.data.len()).filter(|&i| self.data[i].is_none()) {
+            let candidates = self.candidates(idx);
+            if best.is_none_or(|(_, b)| candidates.count_ones() < b.count_ones()) {
+                best = Some((idx, candidates));
+                if candidates.count_ones() <= 1 {
+                    break
+                }
+            }
+        }
+        best
+    }
+
+    fn place(&mut self, idx: Index, value: Value) {
+        self.data[idx] = Some(value);
+        for &unit in self.geometry.cell_units(idx) {
+            self.used[unit] |= 1 << value;
+        }
+    }
+
+    fn clear(&mut self, idx: Index, value: Value) {
+        self.data[idx] = None;
+        for &unit in self.geometry.cell_units(idx) {
+            self.used[unit] &= !(1 << value);
+        }
+    }
+
+    fn run(&mut self) {
+        if self.count >= self.limit {
+            return
+        }
+
+        let (idx, candidates) = match self.next_cell() {
+            Some(cell) => cell,
+            None => {
+                self.count += 1;
+                if self.first.is_none() {
+                    self.first = Some(self.data.clone());
+                }
+                return
+            }
+        };
+
+        for value in self.geometry.values() {
+            if candidates & (1 << value) == 0 {
+                continue
+            }
+            self.place(idx, value);
+            self.run();
+            self.clear(idx, value);
+
+            if self.count >= self.limit {
+                return
+            }
+        }
+    }
+}
+
+/// Counts the solutions of a puzzle by exhaustive search, stopping once `limit` is reached.
+///
+/// A grid whose givens already conflict has no solutions.
+pub fn count_solutions(data: &Data, limit: usize) -> usize {
+    match Search::new(data, limit) {
+        Some(mut search) => {
+            search.run();
+            search.count
+        },
+        None => 0
+    }
+}
+
+/// Returns the first solution found by search, if there is one.
+pub fn first_solution(data: &Data) -> Option<Data> {
+    let mut search = Search::new(data, 1)?;
+    search.run();
+    search.first
+}
+
+/// True when the puzzle has exactly one solution.
+pub fn has_unique_solution(data: &Data) -> bool {
+    count_solutions(data, 2) == 1
+}
+
+/// The unique solution of a puzzle, or the reason there isn't one.
+pub fn solve_unique(data: &Data) -> Result<Data, SudokuError> {
+    let mut search = Search::new(data, 2).ok_or(SudokuError::Unsolvable)?;
+    search.run();
+    match search.count {
+        0 => Err(SudokuError::Unsolvable),
+        1 => Ok(search.first.unwrap()),
+        _ => Err(SudokuError::MultipleSolutions)
+    }
+}
@@ -0,0 +1,139 @@
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{Data, Status, Strategy, SudokuError};
+use crate::collection::Entry;
+use crate::rating::{Rating, rate_with};
+
+/// The outcome of solving one entry of a collection.
+#[derive(Debug)]
+pub struct PuzzleResult {
+    pub entry: Entry,
+    pub outcome: Result<(Data, Rating), SudokuError>,
+    /// Time spent solving this entry
+    pub elapsed: Duration
+}
+
+impl PuzzleResult {
+    /// A one word summary of the outcome, used in the results file.
+    pub fn status(&self) -> &'static str {
+        match &self.outcome {
+            Ok(_) => Status::Solved.name(),
+            Err(err) => Status::from_error(err).name()
+        }
+    }
+}
+
+/// Parses, validates, solves and rates a single entry, rating it by the given strategies.
+pub fn solve_entry(entry: &Entry, strategies: &[Strategy]) -> Result<(Data, Rating), SudokuError> {
+    rate_with(&entry.data()?, strategies)
+}
+
+fn solve_timed(entry: &Entry, strategies: &[Strategy]) -> PuzzleResult {
+    let start = Instant::now();
+    let outcome = solve_entry(entry, strategies);
+    PuzzleResult {entry: entry.clone(), outcome, elapsed: start.elapsed()}
+}
+
+/// Solves every entry across `threads` worker threads, returning the results in entry order.
+pub fn run_batch(entries: &[Entry], threads: usize, strategies: &[Strategy]) -> Vec<PuzzleResult> {
+    let next = AtomicUsize::new(0);
+
+    let mut indexed: Vec<(usize, PuzzleResult)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.max(1)).map(|_| scope.spawn(|| {
+            let mut results = vec!();
+            loop {
+                let n = next.fetch_add(1, Ordering::Relaxed);
+                match entries.get(n) {
+                    Some(entry) => results.push((n, solve_timed(entry, strategies))),
+                    None => return results
+                }
+            }
+        })).collect();
+
+        workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+    });
+
+    indexed.sort_by_key(|(n, _)| *n);
+    indexed.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Aggregate timings for a batch run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Throughput {
+    pub puzzles: usize,
+    /// Wall clock time of the whole run
+    pub elapsed: Duration,
+    pub per_second: f64,
+    pub median: Duration,
+    pub p99: Duration
+}
+
+impl Throughput {
+    pub fn from_results(results: &[PuzzleResult], elapsed: Duration) -> Throughput {
+        let mut times: Vec<Duration> = results.iter().map(|r| r.elapsed).collect();
+        times.sort();
+
+        // Nearest rank percentile, so the p99 of a small batch is its slowest puzzle.
+        let percentile = |p: usize| match times.len() {
+            0 => Duration::ZERO,
+            n => times[(p * n).div_ceil(100).max(1) - 1]
+        };
+
+        let seconds = elapsed.as_secs_f64();
+        Throughput {
+            puzzles: results.len(),
+            elapsed,
+            per_second: if seconds > 0.0 { results.len() as f64 / seconds } else { 0.0 },
+            median: percentile(50),
+            p99: percentile(99)
+        }
+    }
+}
+
+/// Writes one tab separated line per result: line, puzzle, status, rating, solution and metadata,
+/// with `-` for any missing field.
+pub fn write_results<W: Write>(results: &[PuzzleResult], mut writer: W) -> io::Result<()> {
+    writeln!(writer, "# line\tpuzzle\tstatus\trating\tsolution\tmetadata")?;
+
+    for result in results {
+        let (rating, solution) = match &result.outcome {
+            Ok((solution, rating)) => (rating.name().to_string(), solution.to_string()),
+            Err(_) => ("-".into(), "-".into())
+        };
+        writeln!(
+            writer, "{}\t{}\t{}\t{}\t{}\t{}",
+            result.entry.line, result.entry.puzzle, result.status(), rating, solution,
+            result.entry.metadata.as_deref().unwrap_or("-")
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collection::parse_collection;
+
+    #[test]
+    fn test_parallel_order() {
+        let content = "..5....4...4..35.8.68..43.1...68..3...2...8...3..29...5.72..18.6.95..4...8....2.. one\n\
+            .................................................................................\n\
+            4.....8.5.3..........7......2.....6.....8.4......1.......6.3.7.5..2.....1.4...... three\n";
+        let entries = parse_collection(content);
+        let results = run_batch(&entries, 3, &Strategy::ALL);
+
+        assert_eq!(results.iter().map(|r| r.status()).collect::<Vec<_>>(), vec!("solved", "multiple", "solved"));
+        assert_eq!(results[2].entry.metadata, Some("three".into()));
+
+        let throughput = Throughput::from_results(&results, Duration::from_secs(1));
+        assert_eq!(throughput.puzzles, 3);
+        assert_eq!(throughput.p99, results.iter().map(|r| r.elapsed).max().unwrap());
+
+        // With naked singles only the last one stalls, and rates as needing search
+        let results = run_batch(&entries[2..], 1, &[Strategy::NakedSingle]);
+        assert_eq!(results[0].outcome.as_ref().unwrap().1, Rating::Fiendish);
+    }
+}
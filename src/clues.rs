@@ -0,0 +1,313 @@
+use crate::{Constraint, Data, Geometry, Index, Value, Possibles, SudokuError, Unit};
+use crate::killer::{combinations, restrict_sum};
+
+/// The directions a Little Killer clue can point along a diagonal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum Direction {
+    DownRight,
+    DownLeft,
+    UpRight,
+    UpLeft
+}
+
+impl Direction {
+    const ALL: [Direction; 4] = [Direction::DownRight, Direction::DownLeft, Direction::UpRight, Direction::UpLeft];
+
+    /// The row and column step from one cell of the diagonal to the next.
+    pub fn step(&self) -> (isize, isize) {
+        match self {
+            Direction::DownRight => (1, 1),
+            Direction::DownLeft => (1, -1),
+            Direction::UpRight => (-1, 1),
+            Direction::UpLeft => (-1, -1)
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Direction::DownRight => "down-right",
+            Direction::DownLeft => "down-left",
+            Direction::UpRight => "up-right",
+            Direction::UpLeft => "up-left"
+        }
+    }
+
+    /// The arrow for the direction in the text grid.
+    pub fn arrow(&self) -> char {
+        match self {
+            Direction::DownRight => '↘',
+            Direction::DownLeft => '↙',
+            Direction::UpRight => '↗',
+            Direction::UpLeft => '↖'
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Direction> {
+        Direction::ALL.into_iter().find(|direction| direction.name() == name)
+    }
+}
+
+/// The rules a clue written outside the grid can carry.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum ClueKind {
+    /// The digits between the 1 and the largest digit of a row or column add up to `sum`
+    Sandwich { unit: Unit, index: Index, sum: usize },
+    /// The digits along the diagonal from `start`, which may repeat, add up to `sum`
+    LittleKiller { start: Index, direction: Direction, sum: usize }
+}
+
+/// A clue written outside the grid, along with the cells it applies to in order from the clue.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Clue {
+    kind: ClueKind,
+    cells: Vec<Index>
+}
+
+impl Clue {
+    /// Fails unless a sandwich names a row or column of the board, a Little Killer diagonal
+    /// starts at the edge, and either sum can be made.
+    pub fn new(kind: ClueKind, geometry: &Geometry) -> Result<Clue, SudokuError> {
+        let size = geometry.size();
+        let (cells, possible) = match &kind {
+            ClueKind::Sandwich {unit, index, sum} => {
+                if !matches!(unit, Unit::Row | Unit::Column) || geometry.unit(*unit, *index).is_empty() {
+                    return Err(SudokuError::InvalidGeometry(format!("no {:?} {} for a sandwich clue", unit, index)));
+                }
+                (geometry.unit(*unit, *index).to_vec(), *sum <= (2..size).sum::<usize>())
+            },
+            ClueKind::LittleKiller {start, direction, sum} => {
+                let (rows, columns) = direction.step();
+                if *start >= geometry.cell_count() || geometry.step(*start, (-rows, -columns)).is_some() {
+                    return Err(SudokuError::InvalidGeometry(format!("the diagonal from {} does not start at the edge", start)));
+                }
+                let mut cells = vec!(*start);
+                while let Some(next) = geometry.step(*cells.last().unwrap(), (rows, columns)) {
+                    cells.push(next);
+                }
+                let length = cells.len();
+                (cells, (length..=length * size).contains(sum))
+            }
+        };
+        let clue = Clue {kind, cells};
+        if !possible {
+            return Err(SudokuError::InvalidGeometry(format!("no digits make the clue sum {}", clue.sum())));
+        }
+        Ok(clue)
+    }
+
+    pub fn kind(&self) -> &ClueKind {
+        &self.kind
+    }
+
+    pub fn sum(&self) -> usize {
+        match self.kind {
+            ClueKind::Sandwich {sum, ..} | ClueKind::LittleKiller {sum, ..} => sum
+        }
+    }
+}
+
+impl Constraint for Clue {
+    fn name(&self) -> &str {
+        match self.kind {
+            ClueKind::Sandwich {..} => "sandwich",
+            ClueKind::LittleKiller {..} => "little killer"
+        }
+    }
+
+    fn cells(&self) -> &[Index] {
+        &self.cells
+    }
+
+    fn allows(&self, data: &Data, idx: Index, value: Value) -> bool {
+        let (cells, size) = (self.cells.as_slice(), data.geometry().size());
+        let placed = |cell: Index| if cell == idx { Some(value) } else { data[cell] };
+        let total = |cells: &[Index]| -> (usize, usize) {
+            let digits: Vec<Value> = cells.iter().filter_map(|&cell| placed(cell)).collect();
+            (digits.iter().map(|&digit| digit as usize).sum(), cells.len() - digits.len())
+        };
+
+        match self.kind {
+            ClueKind::Sandwich {sum, ..} => {
+                let find = |digit: usize| cells.iter().position(|&cell| placed(cell) == Some(digit as Value));
+                let (Some(first), Some(second)) = (find(1), find(size)) else {
+                    return true
+                };
+                // The digits in between are distinct, and neither 1 nor the largest
+                let (placed_sum, empty) = total(&cells[first.min(second) + 1..first.max(second)]);
+                let lowest = empty * (empty + 3) / 2;
+                let highest = empty * (size - 1) - empty * empty.saturating_sub(1) / 2;
+                (placed_sum + lowest..=placed_sum + highest).contains(&sum)
+            },
+            ClueKind::LittleKiller {sum, ..} => {
+                let (placed_sum, empty) = total(cells);
+                (placed_sum + empty..=placed_sum + empty * size).contains(&sum)
+            }
+        }
+    }
+
+    /// Narrows a sandwich to the placements of the 1 and largest digit which leave room for
+    /// the sum between them, and a Little Killer diagonal to the digits within reach of it.
+    fn prune(&self, data: &Data, possibles: &mut Possibles) -> Vec<(Index, Value)> {
+        let mut eliminated = vec!();
+        match self.kind {
+            ClueKind::Sandwich {sum, ..} => restrict_sandwich(data, possibles, &self.cells, sum, &mut eliminated),
+            ClueKind::LittleKiller {sum, ..} => restrict_sum(data, possibles, &self.cells, sum, &mut eliminated)
+        }
+        eliminated
+    }
+}
+
+// Narrows a row or column to the placements of its 1 and largest digit which leave room for
+// the sandwich sum between them.
+fn restrict_sandwich(data: &Data, possibles: &mut Possibles, cells: &[Index], sum: usize, eliminated: &mut Vec<(Index, Value)>) {
+    let size = data.geometry().size();
+    let masks: Vec<u32> = cells.iter().map(|&idx| match data[idx] {
+        Some(value) => 1 << value,
+        None => possibles.candidates(&idx).into_iter().flatten().fold(0, |acc, value| acc | 1 << value)
+    }).collect();
+    let ends = 1 << 1 | 1 << size;
+
+    let mut firsts = 0u32;
+    let mut lasts = 0u32;
+    let mut between = vec!(0u32; cells.len());
+    let mut always_between = vec!(true; cells.len());
+    let holds = |n: usize, digit: usize| masks[n] & 1 << digit != 0;
+    for first in (0..cells.len()).filter(|&n| holds(n, 1)) {
+        for last in (0..cells.len()).filter(|&n| n != first && holds(n, size)) {
+            let range = first.min(last) + 1..first.max(last);
+            let digits = combinations(sum, range.len(), size, ends).into_iter()
+                .filter(|&combination| masks[range.clone()].iter().all(|mask| mask & combination != 0))
+                .fold(0, |acc, combination| acc | combination);
+            let fits = if range.is_empty() { sum == 0 } else { digits != 0 };
+            if !fits {
+                continue
+            }
+
+            firsts |= 1 << first;
+            lasts |= 1 << last;
+            for n in 0..cells.len() {
+                if range.contains(&n) {
+                    between[n] |= digits;
+                } else {
+                    always_between[n] = false;
+                }
+            }
+        }
+    }
+
+    for (n, &idx) in cells.iter().enumerate() {
+        if data[idx].is_some() {
+            continue
+        }
+        let candidates: Vec<Value> = possibles.candidates(&idx).into_iter().flatten().copied().collect();
+        for value in candidates {
+            let keep = match value as usize {
+                1 => firsts & 1 << n != 0,
+                digit if digit == size => lasts & 1 << n != 0,
+                _ => !always_between[n] || between[n] & 1 << value != 0
+            };
+            if !keep && possibles.update(&idx, &value) {
+                eliminated.push((idx, value));
+            }
+        }
+    }
+}
+
+/// Reads a clue line onto a geometry: `sandwich <row|column> <index> <sum>`, or `little <sum>
+/// <start> <direction>` with a direction such as `down-right`.  The line number is 1-based,
+/// for errors.
+pub fn parse_clue(geometry: Geometry, line: &str, line_number: usize) -> Result<Geometry, SudokuError> {
+    let parse_error = |message: &str| SudokuError::Parse {line: line_number, column: 1, message: message.into()};
+    let number = |field: &str| field.parse::<usize>().map_err(|_| parse_error("unable to parse clue number"));
+
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let kind = match fields.as_slice() {
+        ["sandwich", unit, index, sum] => {
+            let unit = match *unit {
+                "row" => Unit::Row,
+                "column" => Unit::Column,
+                _ => return Err(parse_error("expected a sandwich `row` or `column`"))
+            };
+            ClueKind::Sandwich {unit, index: number(index)?, sum: number(sum)?}
+        },
+        ["little", sum, start, direction] => {
+            let direction = Direction::from_name(direction)
+                .ok_or_else(|| parse_error("expected `down-right`, `down-left`, `up-right` or `up-left`"))?;
+            ClueKind::LittleKiller {start: number(start)?, direction, sum: number(sum)?}
+        },
+        _ => return Err(parse_error("expected `sandwich <row|column> <index> <sum>` or `little <sum> <start> <direction>`"))
+    };
+    let clue = Clue::new(kind, &geometry)?;
+    geometry.with_constraint(clue)
+}
+
+/// Writes the clues of a geometry as lines, the counterpart of `parse_clue`.
+pub fn write_clues(geometry: &Geometry) -> String {
+    geometry.constraints_of::<Clue>().map(|clue| match clue.kind() {
+        ClueKind::Sandwich {unit, index, sum} => {
+            let unit = if *unit == Unit::Row { "row" } else { "column" };
+            format!("sandwich {} {} {}\n", unit, index, sum)
+        },
+        ClueKind::LittleKiller {start, direction, sum} => format!("little {} {} {}\n", sum, start, direction.name())
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::validate;
+    use crate::backtrack::first_solution;
+    use crate::constraint::remove_constraint_candidates;
+
+    #[test]
+    fn test_sandwich() {
+        let geometry = Geometry::new(3, 3).unwrap();
+        let clue = Clue::new(ClueKind::Sandwich {unit: Unit::Row, index: 0, sum: 0}, &geometry).unwrap();
+        let geometry = geometry.with_constraint(clue).unwrap();
+        let mut data = Data::with_geometry(Arc::new(geometry));
+        data[3] = Some(1);
+        let mut possibles = Possibles::from_data(&data);
+
+        // With nothing in between, the 9 must sit right next to the 1
+        remove_constraint_candidates(&data, &mut possibles);
+        assert_eq!(possibles.to_cells()[&2], vec!(2, 3, 4, 5, 6, 7, 8, 9));
+        assert!(!possibles.to_cells()[&0].contains(&9));
+        let nines: Vec<Index> = (0..9).filter(|idx| possibles.to_cells().get(idx).is_some_and(|c| c.contains(&9))).collect();
+        assert_eq!(nines, vec!(2, 4));
+
+        let solution = first_solution(&data).unwrap();
+        assert!(validate(&solution).is_empty());
+        assert_eq!(solution[2].max(solution[4]), Some(9));
+    }
+
+    #[test]
+    fn test_little_killer() {
+        let geometry = Geometry::new(3, 3).unwrap();
+        let clue = Clue::new(ClueKind::LittleKiller {start: 2, direction: Direction::DownLeft, sum: 6}, &geometry).unwrap();
+        assert_eq!(clue.cells(), &[2, 10, 18]);
+        let geometry = geometry.with_constraint(clue).unwrap();
+        let data = Data::with_geometry(Arc::new(geometry));
+        let mut possibles = Possibles::from_data(&data);
+
+        remove_constraint_candidates(&data, &mut possibles);
+        assert_eq!(possibles.to_cells()[&10], vec!(1, 2, 3, 4));
+
+        let solution = first_solution(&data).unwrap();
+        assert_eq!([2, 10, 18].iter().map(|&idx| solution[idx].unwrap()).sum::<Value>(), 6);
+    }
+
+    #[test]
+    fn test_parse_clue() {
+        let geometry = Geometry::new(3, 3).unwrap();
+        let geometry = parse_clue(geometry, "sandwich column 4 35", 1).unwrap();
+        let geometry = parse_clue(geometry, "little 12 72 up-right", 2).unwrap();
+        assert_eq!(write_clues(&geometry), "sandwich column 4 35\nlittle 12 72 up-right\n");
+        assert!(matches!(parse_clue(geometry.clone(), "sandwich column 4 36", 3), Err(SudokuError::InvalidGeometry(_))));
+        assert!(matches!(parse_clue(geometry, "little 12 72 sideways", 4), Err(SudokuError::Parse {line: 4, ..})));
+    }
+}
@@ -0,0 +1,81 @@
+use std::fs;
+
+use crate::{Data, SudokuError};
+
+/// One puzzle from a collection file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// The 1-based line of the file the puzzle was read from
+    pub line: usize,
+    pub puzzle: String,
+    /// Anything following the puzzle on its line, such as a rating or a name
+    pub metadata: Option<String>
+}
+
+impl Entry {
+    /// Parses the puzzle string, reporting errors against the entry's line in the file.
+    pub fn data(&self) -> Result<Data, SudokuError> {
+        self.puzzle.parse().map_err(|err| match err {
+            SudokuError::Parse { column, message, .. } => SudokuError::Parse {line: self.line, column, message},
+            err => err
+        })
+    }
+}
+
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || c == ';' || c == ','
+}
+
+/// Splits a collection into entries, one puzzle per line in the 81 character format.
+///
+/// Blank lines and comment lines starting with `#` are skipped.  The puzzle ends at the first
+/// whitespace, `;` or `,`, and the rest of the line is kept as the entry's metadata.
+pub fn parse_collection(content: &str) -> Vec<Entry> {
+    let mut entries = vec!();
+
+    for (n, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue
+        }
+
+        let (puzzle, rest) = match line.find(is_separator) {
+            Some(split) => line.split_at(split),
+            None => (line, "")
+        };
+        let metadata = rest.trim_start_matches(is_separator).trim_end();
+
+        entries.push(Entry {
+            line: n + 1,
+            puzzle: puzzle.into(),
+            metadata: if metadata.is_empty() { None } else { Some(metadata.into()) }
+        });
+    }
+    entries
+}
+
+pub fn read_collection(filename: &str) -> Result<Vec<Entry>, SudokuError> {
+    let content = fs::read_to_string(filename)?;
+    Ok(parse_collection(&content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_collection() {
+        let content = "# a comment\n\n\
+            ..5....4...4..35.8.68..43.1...68..3...2...8...3..29...5.72..18.6.95..4...8....2..  3.4 puzzle two\n\
+            ..5....4...4..35.8.68..43.1...68..3...2...8...3..29...5.72..18.6.95..4...8....2..;hard\n\
+            ..5....4\n";
+        let entries = parse_collection(content);
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].line, 3);
+        assert_eq!(entries[0].metadata, Some("3.4 puzzle two".into()));
+        assert_eq!(entries[1].metadata, Some("hard".into()));
+        assert!(entries[1].data().is_ok());
+        assert!(matches!(entries[2].data(), Err(SudokuError::Parse {line: 5, ..})));
+    }
+}
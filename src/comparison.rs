@@ -0,0 +1,274 @@
+use std::cmp::Ordering;
+use std::slice;
+
+use crate::{Constraint, Data, Geometry, Index, Value, Possibles, SudokuError};
+
+/// The shadings which keep a cell to odd or to even digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum ParityKind {
+    Odd,
+    Even
+}
+
+impl ParityKind {
+    /// True when a digit has this parity.
+    pub fn allows(&self, value: Value) -> bool {
+        (value % 2 == 1) == (*self == ParityKind::Odd)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ParityKind::Odd => "odd",
+            ParityKind::Even => "even"
+        }
+    }
+}
+
+/// A cell shaded odd or even.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Parity {
+    cell: Index,
+    kind: ParityKind
+}
+
+impl Parity {
+    pub fn new(cell: Index, kind: ParityKind) -> Parity {
+        Parity {cell, kind}
+    }
+
+    pub fn cell(&self) -> Index {
+        self.cell
+    }
+
+    pub fn kind(&self) -> ParityKind {
+        self.kind
+    }
+}
+
+impl Constraint for Parity {
+    fn name(&self) -> &str {
+        self.kind.name()
+    }
+
+    fn cells(&self) -> &[Index] {
+        slice::from_ref(&self.cell)
+    }
+
+    fn allows(&self, _data: &Data, _idx: Index, value: Value) -> bool {
+        self.kind.allows(value)
+    }
+}
+
+/// A greater-than sign between two orthogonally neighbouring cells, pointing at the lesser.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Inequality {
+    cells: [Index; 2]
+}
+
+impl Inequality {
+    pub fn new(greater: Index, lesser: Index) -> Inequality {
+        Inequality {cells: [greater, lesser]}
+    }
+
+    pub fn greater(&self) -> Index {
+        self.cells[0]
+    }
+
+    pub fn lesser(&self) -> Index {
+        self.cells[1]
+    }
+}
+
+// The digits a cell may still hold.
+fn digits(data: &Data, possibles: &Possibles, idx: Index) -> Vec<Value> {
+    match data[idx] {
+        Some(value) => vec!(value),
+        None => possibles.candidates(&idx).into_iter().flatten().copied().collect()
+    }
+}
+
+// The cells a chain of signs leads to from `idx`, always further in the `towards` direction:
+// every cell known to be smaller than it for `Ordering::Greater`, or larger for `Less`.
+fn chain(geometry: &Geometry, idx: Index, towards: Ordering) -> Vec<Index> {
+    let mut reached = vec!();
+    let mut stack = vec!(idx);
+    while let Some(cell) = stack.pop() {
+        for inequality in geometry.constraints_at::<Inequality>(cell) {
+            let next = match towards {
+                Ordering::Greater if inequality.greater() == cell => inequality.lesser(),
+                Ordering::Less if inequality.lesser() == cell => inequality.greater(),
+                _ => continue
+            };
+            if next != idx && !reached.contains(&next) {
+                reached.push(next);
+                stack.push(next);
+            }
+        }
+    }
+    reached
+}
+
+impl Constraint for Inequality {
+    fn name(&self) -> &str {
+        "greater"
+    }
+
+    fn cells(&self) -> &[Index] {
+        &self.cells
+    }
+
+    fn allows(&self, data: &Data, idx: Index, value: Value) -> bool {
+        if idx == self.greater() {
+            data[self.lesser()].is_none_or(|other| value > other)
+        } else {
+            data[self.greater()].is_none_or(|other| value < other)
+        }
+    }
+
+    /// Removes the candidates on either side of the sign which no digit left on the other side
+    /// can be compared with.
+    ///
+    /// A cell also stays clear of as many digits as there are cells in its chains of signs which
+    /// all see each other, since those hold distinct digits all below (or all above) it.  The
+    /// bounds are carried along the chains as the solver applies each sign in turn.
+    fn prune(&self, data: &Data, possibles: &mut Possibles) -> Vec<(Index, Value)> {
+        let geometry = data.geometry();
+        let size = geometry.size() as Value;
+        let mut eliminated = vec!();
+
+        let mut remove = |possibles: &mut Possibles, idx: Index, keep: &dyn Fn(Value) -> bool| {
+            for value in digits(data, possibles, idx).into_iter().filter(|&value| !keep(value)) {
+                if data[idx].is_none() && possibles.update(&idx, &value) {
+                    eliminated.push((idx, value));
+                }
+            }
+        };
+
+        // Digits which see each other in a chain are distinct, so they take up that many digits
+        for idx in self.cells.into_iter().filter(|&idx| data[idx].is_none()) {
+            for towards in [Ordering::Greater, Ordering::Less] {
+                let mut distinct: Vec<Index> = vec!();
+                for other in chain(geometry, idx, towards) {
+                    if distinct.iter().all(|&cell| geometry.around(cell).contains(&other)) {
+                        distinct.push(other);
+                    }
+                }
+                let count = distinct.len() as Value;
+                match towards {
+                    Ordering::Greater => remove(possibles, idx, &|value| value > count),
+                    _ => remove(possibles, idx, &|value| value + count <= size)
+                };
+            }
+        }
+
+        let lowest = digits(data, possibles, self.lesser()).into_iter().min().unwrap_or(size);
+        let highest = digits(data, possibles, self.greater()).into_iter().max().unwrap_or(1);
+        remove(possibles, self.greater(), &|value| value > lowest);
+        remove(possibles, self.lesser(), &|value| value < highest);
+        eliminated
+    }
+}
+
+/// Applies a comparison rule line to a geometry: `odd` or `even` then the indices of the
+/// shaded cells, or `greater <index> <index>` for a sign saying the first cell holds the
+/// larger digit.  The line number is 1-based, for errors.
+pub fn parse_comparison(geometry: Geometry, line: &str, line_number: usize) -> Result<Geometry, SudokuError> {
+    let parse_error = |message: &str| SudokuError::Parse {line: line_number, column: 1, message: message.into()};
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let cells = fields[1..].iter().map(|field| field.parse::<Index>().map_err(|_| parse_error("unable to parse comparison cells")))
+        .collect::<Result<Vec<Index>, SudokuError>>()?;
+
+    match (fields[0], cells.as_slice()) {
+        ("greater", &[greater, lesser]) => geometry.with_inequality(Inequality::new(greater, lesser)),
+        ("odd" | "even", cells) if !cells.is_empty() => {
+            let kind = if fields[0] == "odd" { ParityKind::Odd } else { ParityKind::Even };
+            cells.iter().try_fold(geometry, |geometry, &idx| geometry.with_parity(Parity::new(idx, kind)))
+        },
+        _ => Err(parse_error("expected `odd <index> ...`, `even <index> ...` or `greater <index> <index>`"))
+    }
+}
+
+/// Writes the odd/even cells and greater-than signs of a geometry as lines, the counterpart
+/// of `parse_comparison`.
+pub fn write_comparisons(geometry: &Geometry) -> String {
+    let mut out = String::new();
+    for kind in [ParityKind::Odd, ParityKind::Even] {
+        let mut cells: Vec<Index> = geometry.constraints_of::<Parity>().filter(|parity| parity.kind == kind).map(|parity| parity.cell).collect();
+        cells.sort();
+        if !cells.is_empty() {
+            let cells: Vec<String> = cells.iter().map(|idx| idx.to_string()).collect();
+            out += &format!("{} {}\n", kind.name(), cells.join(" "));
+        }
+    }
+    for inequality in geometry.constraints_of::<Inequality>() {
+        out += &format!("greater {} {}\n", inequality.greater(), inequality.lesser());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::backtrack::first_solution;
+    use crate::constraint::remove_constraint_candidates;
+    use crate::validate;
+
+    #[test]
+    fn test_parity() {
+        let geometry = Geometry::new(3, 3).unwrap()
+            .with_parity(Parity::new(0, ParityKind::Even)).unwrap()
+            .with_parity(Parity::new(1, ParityKind::Odd)).unwrap();
+        let mut data = Data::with_geometry(Arc::new(geometry));
+        data[9] = Some(4);
+        let mut possibles = Possibles::from_data(&data);
+
+        remove_constraint_candidates(&data, &mut possibles);
+        assert_eq!(possibles.to_cells()[&0], vec!(2, 6, 8));
+        assert_eq!(possibles.to_cells()[&1], vec!(1, 3, 5, 7, 9));
+
+        let solution = first_solution(&data).unwrap();
+        assert!(validate(&solution).is_empty());
+        assert!(ParityKind::Even.allows(solution[0].unwrap()) && ParityKind::Odd.allows(solution[1].unwrap()));
+    }
+
+    #[test]
+    fn test_inequality_chains() {
+        // 0 > 1 > 2 along the top row, and 0 > 9 below it
+        let geometry = Geometry::new(3, 3).unwrap()
+            .with_inequality(Inequality::new(0, 1)).unwrap()
+            .with_inequality(Inequality::new(1, 2)).unwrap()
+            .with_inequality(Inequality::new(0, 9)).unwrap();
+        let mut data = Data::with_geometry(Arc::new(geometry));
+        data[3] = Some(9);
+        let mut possibles = Possibles::from_data(&data);
+
+        // The bounds settle once every sign has been applied along the chains
+        while !remove_constraint_candidates(&data, &mut possibles).is_empty() {}
+        // Cells 1, 2 and 9 share a box, so the top of the chain needs three digits below it
+        assert_eq!(possibles.to_cells()[&0], vec!(4, 5, 6, 7, 8));
+        assert_eq!(possibles.to_cells()[&1], vec!(2, 3, 4, 5, 6, 7));
+        assert_eq!(possibles.to_cells()[&2], vec!(1, 2, 3, 4, 5, 6));
+        assert_eq!(possibles.to_cells()[&9], vec!(1, 2, 3, 4, 5, 6, 7));
+
+        let solution = first_solution(&data).unwrap();
+        assert!(validate(&solution).is_empty());
+        assert!(solution[0] > solution[1] && solution[1] > solution[2] && solution[0] > solution[9]);
+    }
+
+    #[test]
+    fn test_parse_comparison() {
+        let geometry = Geometry::new(3, 3).unwrap();
+        let geometry = parse_comparison(geometry, "odd 0 4", 1).unwrap();
+        let geometry = parse_comparison(geometry, "greater 10 1", 2).unwrap();
+        let geometry = parse_comparison(geometry, "even 80", 3).unwrap();
+        assert_eq!(write_comparisons(&geometry), "odd 0 4\neven 80\ngreater 10 1\n");
+        assert!(matches!(parse_comparison(geometry.clone(), "even 4", 4), Err(SudokuError::InvalidGeometry(_))));
+        assert!(matches!(parse_comparison(geometry.clone(), "greater 1 10", 5), Err(SudokuError::InvalidGeometry(_))));
+        assert!(matches!(parse_comparison(geometry, "greater 0", 6), Err(SudokuError::Parse {line: 6, ..})));
+    }
+}
@@ -0,0 +1,145 @@
+use std::any::Any;
+use std::fmt;
+
+use crate::{Data, Index, Value, Possibles};
+
+/// A rule over some cells of the grid, added to a geometry with `Geometry::with_constraint`.
+///
+/// Every rule beyond the units and relations is a constraint: killer cages, dots, lines,
+/// outside clues, odd/even cells and greater-than signs, as well as any new variant.  The
+/// solver prunes candidates with them, the backtracking search checks each placement against
+/// them, and `validate` reports the ones the givens break, all without knowing what the rule
+/// is.  Only `name`, `cells` and `allows` are required; the other methods have defaults built
+/// on them.
+pub trait Constraint: Any + fmt::Debug + Send + Sync {
+    /// A short name for the rule, such as `even sum`.
+    fn name(&self) -> &str;
+
+    /// The cells the rule applies to.
+    fn cells(&self) -> &[Index];
+
+    /// True when the digits of the cells must all differ, which makes the cells peers.
+    fn is_distinct(&self) -> bool {
+        false
+    }
+
+    /// True when `value` in cell `idx`, one of `cells`, keeps to the rule given the digits
+    /// placed in the other cells of `data`.  Empty cells should be taken as able to hold
+    /// anything, so that a partly filled grid is only rejected once it cannot be completed.
+    fn allows(&self, data: &Data, idx: Index, value: Value) -> bool;
+
+    /// Removes the candidates the rule rules out, returning the `(index, value)` candidates
+    /// eliminated.  By default, every candidate of an empty cell which `allows` rejects.
+    fn prune(&self, data: &Data, possibles: &mut Possibles) -> Vec<(Index, Value)> {
+        let mut eliminated = vec!();
+        for &idx in self.cells().iter().filter(|&&idx| data[idx].is_none()) {
+            let candidates: Vec<Value> = possibles.candidates(&idx).into_iter().flatten().copied().collect();
+            for value in candidates {
+                if !self.allows(data, idx, value) && possibles.update(&idx, &value) {
+                    eliminated.push((idx, value));
+                }
+            }
+        }
+        eliminated
+    }
+
+    /// True when the digits placed so far don't break the rule.  By default, when `allows`
+    /// accepts every placed digit.
+    fn is_consistent(&self, data: &Data) -> bool {
+        self.cells().iter().all(|&idx| data[idx].is_none_or(|value| self.allows(data, idx, value)))
+    }
+}
+
+impl dyn Constraint {
+    /// The constraint as its own type, such as a `Cage`, if it is one.
+    pub fn downcast_ref<T: Constraint>(&self) -> Option<&T> {
+        (self as &dyn Any).downcast_ref()
+    }
+}
+
+/// Applies every constraint of the geometry in turn.  Returns the `(index, value)` candidates
+/// eliminated.
+pub fn remove_constraint_candidates(data: &Data, possibles: &mut Possibles) -> Vec<(Index, Value)> {
+    data.geometry().constraints().iter()
+        .flat_map(|constraint| constraint.prune(data, possibles))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{Geometry, Solver, Strategy, Violation, validate};
+    use crate::backtrack::first_solution;
+
+    // The digits along the cells add up to an even number.
+    #[derive(Debug)]
+    struct EvenSum(Vec<Index>);
+
+    impl Constraint for EvenSum {
+        fn name(&self) -> &str {
+            "even sum"
+        }
+
+        fn cells(&self) -> &[Index] {
+            &self.0
+        }
+
+        fn allows(&self, data: &Data, idx: Index, value: Value) -> bool {
+            let others: Vec<Value> = self.0.iter().filter(|&&cell| cell != idx).filter_map(|&cell| data[cell]).collect();
+            others.len() + 1 < self.0.len() || (others.iter().sum::<Value>() + value).is_multiple_of(2)
+        }
+    }
+
+    // The cells hold distinct digits, as a diagonal would.
+    #[derive(Debug)]
+    struct Distinct(Vec<Index>);
+
+    impl Constraint for Distinct {
+        fn name(&self) -> &str {
+            "distinct"
+        }
+
+        fn cells(&self) -> &[Index] {
+            &self.0
+        }
+
+        fn is_distinct(&self) -> bool {
+            true
+        }
+
+        fn allows(&self, data: &Data, idx: Index, value: Value) -> bool {
+            self.0.iter().all(|&cell| cell == idx || data[cell] != Some(value))
+        }
+    }
+
+    #[test]
+    fn test_custom_constraints() {
+        let geometry = Geometry::new(3, 3).unwrap()
+            .with_constraint(EvenSum(vec!(0, 1, 2))).unwrap()
+            .with_constraint(Distinct(vec!(0, 40, 80))).unwrap();
+        assert!(geometry.around(0).contains(&80));
+        assert!(Geometry::new(3, 3).unwrap().with_constraint(EvenSum(vec!(0, 81))).is_err());
+
+        let geometry = Arc::new(geometry);
+        let mut data = Data::with_geometry(geometry.clone());
+        data[0] = Some(1);
+        data[1] = Some(2);
+        let mut possibles = Possibles::from_data(&data);
+        remove_constraint_candidates(&data, &mut possibles);
+        assert_eq!(possibles.to_cells()[&2], vec!(3, 5, 7, 9));
+
+        let mut solver = Solver::new(data.clone()).unwrap();
+        solver.solve().unwrap();
+        assert!(solver.steps().iter().any(|step| step.strategy == Strategy::Constraints));
+
+        let solution = first_solution(&data).unwrap();
+        assert!(validate(&solution).is_empty());
+        assert!((solution[2].unwrap() + 3).is_multiple_of(2));
+
+        data[2] = Some(4);
+        assert_eq!(validate(&data), vec!(Violation::Constraint(0)));
+        assert!(first_solution(&data).is_none());
+    }
+}
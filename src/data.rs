@@ -0,0 +1,297 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::{DataCell, Geometry, SudokuError};
+#[cfg(feature = "serde")]
+use crate::{Index, Relation, Unit};
+#[cfg(feature = "serde")]
+use crate::utils::{is_written_rule, parse_rules, write_rules};
+
+/// The puzzle board, as one cell per row and column of its geometry, indexed from the top
+/// left, counting rows to the right.
+///
+/// Dereferences to the underlying cells, so cells are read and written by index.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Data {
+    geometry: Arc<Geometry>,
+    cells: Vec<DataCell>
+}
+
+impl Data {
+    /// An empty classic 9×9 grid.
+    pub fn empty() -> Data {
+        Data::with_geometry(Geometry::classic())
+    }
+
+    /// An empty grid of any geometry.
+    pub fn with_geometry(geometry: Arc<Geometry>) -> Data {
+        let cells = vec!(None; geometry.cell_count());
+        Data {geometry, cells}
+    }
+
+    pub fn geometry(&self) -> &Arc<Geometry> {
+        &self.geometry
+    }
+
+    /// The number of filled cells.
+    pub fn count(&self) -> usize {
+        self.cells.iter().filter(|item| item.is_some()).count()
+    }
+
+    /// Parses the one-line format (see `FromStr`) for a given geometry, which is needed when
+    /// the boxes are not the usual shape for the side length, such as 2×3 rather than 3×2.
+    pub fn parse_with(geometry: Arc<Geometry>, s: &str) -> Result<Data, SudokuError> {
+        let cells = read_cells(s)?;
+        Data::from_cells(geometry, cells)
+    }
+
+    fn from_cells(geometry: Arc<Geometry>, cells: Vec<(char, usize, usize)>) -> Result<Data, SudokuError> {
+        let mut data = Data::with_geometry(geometry);
+        if let Some(&(_, line, column)) = cells.last().filter(|_| cells.len() != data.len()) {
+            return Err(SudokuError::Parse {line, column, message: format!("expected {} cells, found {}", data.len(), cells.len())});
+        }
+
+        for (idx, (c, line, column)) in cells.into_iter().enumerate() {
+            if matches!(c, '.' | '0' | '_') {
+                continue
+            }
+            data[idx] = Some(data.geometry.parse_digit(c).ok_or_else(|| SudokuError::Parse {
+                line, column, message: format!("digit `{}` out of range for a {}x{} grid", c, data.geometry.size(), data.geometry.size())
+            })?);
+        }
+        Ok(data)
+    }
+}
+
+// The cell characters of the one-line format with their 1-based line and column.
+fn read_cells(s: &str) -> Result<Vec<(char, usize, usize)>, SudokuError> {
+    let mut cells = vec!();
+    let (mut line, mut column) = (1, 0);
+
+    for c in s.chars() {
+        column += 1;
+        if c == '\n' {
+            line += 1;
+            column = 0;
+            continue
+        }
+        if c.is_whitespace() || c == '|' || c == '-' || c == '+' {
+            continue
+        }
+        if !matches!(c, '.' | '0' | '_' | '1'..='9' | 'A'..='P' | 'a'..='p') {
+            return Err(SudokuError::Parse {line, column, message: format!("unexpected character `{}`", c)});
+        }
+        cells.push((c, line, column));
+    }
+
+    if cells.is_empty() {
+        return Err(SudokuError::Parse {line, column, message: "no cells found".into()});
+    }
+    Ok(cells)
+}
+
+impl Default for Data {
+    fn default() -> Data {
+        Data::empty()
+    }
+}
+
+impl From<[DataCell; 81]> for Data {
+    fn from(cells: [DataCell; 81]) -> Data {
+        Data {geometry: Geometry::classic(), cells: cells.to_vec()}
+    }
+}
+
+impl Deref for Data {
+    type Target = [DataCell];
+
+    fn deref(&self) -> &[DataCell] {
+        &self.cells
+    }
+}
+
+impl DerefMut for Data {
+    fn deref_mut(&mut self) -> &mut [DataCell] {
+        &mut self.cells
+    }
+}
+
+/// Parses the one-line format: a character per cell, with `.`, `0` or `_` for blank cells and
+/// `1`-`9` then `A`-`P` for the digits.
+///
+/// The size comes from the number of cells (16 for 4×4, 81 for 9×9, 256 for 16×16 and so on),
+/// with the usual box shape for that size, see `Geometry::for_size`.  Whitespace and the grid
+/// drawing characters `|`, `-` and `+` are skipped, so a pasted grid reads the same as a single line.
+impl FromStr for Data {
+    type Err = SudokuError;
+
+    fn from_str(s: &str) -> Result<Data, SudokuError> {
+        let cells = read_cells(s)?;
+        let &(_, line, column) = cells.last().unwrap();
+        let geometry = Geometry::from_cell_count(cells.len()).ok_or_else(|| SudokuError::Parse {
+            line, column, message: format!("expected a square number of cells such as 81, found {}", cells.len())
+        })?;
+        Data::from_cells(geometry, cells)
+    }
+}
+
+// A grid on any board other than the usual one for its number of cells: the board shape, its
+// extra units and relations, and its constraints as rule lines, see `utils::write_rules`.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Board {
+    grid: String,
+    box_width: usize,
+    box_height: usize,
+    grids: Vec<(usize, usize)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    regions: Option<Vec<Index>>,
+    #[serde(default)]
+    units: Vec<(Unit, Vec<Index>)>,
+    #[serde(default)]
+    relations: Vec<Relation>,
+    #[serde(default)]
+    rules: String
+}
+
+#[cfg(feature = "serde")]
+impl Board {
+    fn into_data(self) -> Result<Data, SudokuError> {
+        let mut geometry = match self.regions {
+            Some(regions) => Geometry::jigsaw(regions)?,
+            None => Geometry::overlapping(self.box_width, self.box_height, self.grids)?
+        };
+        for (unit, idxs) in self.units {
+            geometry = geometry.with_unit(unit, idxs)?;
+        }
+        for relation in self.relations {
+            geometry = geometry.with_relation(relation);
+        }
+        let geometry = parse_rules(geometry, &self.rules)?;
+        Data::parse_with(Arc::new(geometry), &self.grid)
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum Serialized {
+    Line(String),
+    Board(Board)
+}
+
+/// Serializes as the one-line string, see `FromStr`, when the grid is on the usual board for
+/// its number of cells.  Any other board, such as a jigsaw, a Samurai or a killer, serializes
+/// as an object holding that string along with the board shape, extra units, relations and
+/// rules.  Constraints other than the cages, dots, lines, clues and comparisons of puzzle
+/// files can't be written, and fail to serialize.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Data {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let geometry = &self.geometry;
+        if Geometry::from_cell_count(self.len()).is_some_and(|usual| usual == *geometry) {
+            return serializer.collect_str(self)
+        }
+        if let Some(constraint) = geometry.constraints().iter().find(|constraint| !is_written_rule(constraint.as_ref())) {
+            return Err(serde::ser::Error::custom(format!("unable to serialize the `{}` constraint", constraint.name())));
+        }
+
+        let units = geometry.units()
+            .filter(|(unit, _, _)| !matches!(unit, Unit::Row | Unit::Column | Unit::Subsquare | Unit::Region))
+            .map(|(unit, _, idxs)| (unit, idxs.to_vec()))
+            .collect();
+        let board = Board {
+            grid: self.to_string(),
+            box_width: geometry.box_width(),
+            box_height: geometry.box_height(),
+            grids: geometry.grids().to_vec(),
+            regions: geometry.is_jigsaw().then(|| geometry.regions().to_vec()),
+            units,
+            relations: geometry.relations().to_vec(),
+            rules: write_rules(geometry)
+        };
+        board.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Data {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Data, D::Error> {
+        match Serialized::deserialize(deserializer)? {
+            Serialized::Line(s) => s.parse(),
+            Serialized::Board(board) => board.into_data()
+        }.map_err(serde::de::Error::custom)
+    }
+}
+
+/// Writes the one-line format, with `.` for blank cells.
+impl fmt::Display for Data {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for item in self.cells.iter() {
+            match item {
+                Some(value) => write!(f, "{}", self.geometry.digit_char(*value))?,
+                None => write!(f, ".")?
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let line = "..5....4...4..35.8.68..43.1...68..3...2...8...3..29...5.72..18.6.95..4...8....2..";
+        let data: Data = line.parse().unwrap();
+
+        assert_eq!(data[2], Some(5));
+        assert_eq!(data[0], None);
+        assert_eq!(data.to_string(), line);
+    }
+
+    #[test]
+    fn test_pasted_grid() {
+        let grid = "
+            0 0 5 | 0 0 0 | 0 4 0
+            0 0 4 | 0 0 3 | 5 0 8
+            0 6 8 | 0 0 4 | 3 0 1
+            ------+-------+------
+            _ _ _ | 6 8 _ | _ 3 _
+            _ _ 2 | _ _ _ | 8 _ _
+            _ 3 _ | _ 2 9 | _ _ _
+            ------+-------+------
+            5 . 7 | 2 . . | 1 8 .
+            6 . 9 | 5 . . | 4 . .
+            . 8 . | . . . | 2 . .
+        ";
+        let data: Data = grid.parse().unwrap();
+
+        assert_eq!(data.to_string(), "..5....4...4..35.8.68..43.1...68..3...2...8...3..29...5.72..18.6.95..4...8....2..");
+        assert!(matches!("12x".parse::<Data>(), Err(SudokuError::Parse {line: 1, column: 3, ..})));
+        assert!("123".parse::<Data>().is_err());
+    }
+
+    #[test]
+    fn test_other_sizes() {
+        let data: Data = "1.3..2..3..2.4..".parse().unwrap();
+        assert_eq!(data.geometry().size(), 4);
+        assert_eq!(data[15], None);
+        assert_eq!(data[13], Some(4));
+
+        let line = format!("{}G{}", "1".repeat(16), ".".repeat(239));
+        let data: Data = line.parse().unwrap();
+        assert_eq!((data.geometry().box_width(), data.geometry().box_height()), (4, 4));
+        assert_eq!(data[16], Some(16));
+        assert_eq!(data.to_string(), line);
+
+        let geometry = Arc::new(Geometry::new(2, 3).unwrap());
+        let data = Data::parse_with(geometry, &".".repeat(36)).unwrap();
+        assert_eq!(data.geometry().box_width(), 2);
+        assert!(matches!("1234A.....".parse::<Data>(), Err(SudokuError::Parse {line: 1, column: 10, ..})));
+        assert!(Data::parse_with(Geometry::classic(), &format!("A{}", ".".repeat(80))).is_err());
+    }
+}
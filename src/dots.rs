@@ -0,0 +1,282 @@
+use crate::{Constraint, Data, Geometry, Index, Value, Possibles, SudokuError};
+
+/// The marks which may join two orthogonally neighbouring cells: Kropki dots, and the X and V
+/// of XV puzzles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum DotKind {
+    /// The digits are consecutive
+    White,
+    /// One digit is double the other
+    Black,
+    /// The digits add up to 10
+    X,
+    /// The digits add up to 5
+    V
+}
+
+impl DotKind {
+    /// True when a pair of digits fits a dot of this kind.
+    pub fn joins(&self, a: Value, b: Value) -> bool {
+        match self {
+            DotKind::White => a.abs_diff(b) == 1,
+            DotKind::Black => a == 2 * b || b == 2 * a,
+            DotKind::X => a + b == 10,
+            DotKind::V => a + b == 5
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DotKind::White => "white",
+            DotKind::Black => "black",
+            DotKind::X => "x",
+            DotKind::V => "v"
+        }
+    }
+
+    /// The character marking the dot in the text grid.
+    pub fn symbol(&self) -> char {
+        match self {
+            DotKind::White => 'o',
+            DotKind::Black => '*',
+            DotKind::X => 'X',
+            DotKind::V => 'V'
+        }
+    }
+}
+
+/// A dot between two orthogonally neighbouring cells, with the lower index first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Dot {
+    cells: [Index; 2],
+    kind: DotKind
+}
+
+impl Dot {
+    pub fn new(first: Index, second: Index, kind: DotKind) -> Dot {
+        Dot {cells: [first.min(second), first.max(second)], kind}
+    }
+
+    pub fn first(&self) -> Index {
+        self.cells[0]
+    }
+
+    pub fn second(&self) -> Index {
+        self.cells[1]
+    }
+
+    pub fn kind(&self) -> DotKind {
+        self.kind
+    }
+}
+
+// The digits a cell may still hold: its own if placed, otherwise its candidates.
+fn digits(data: &Data, possibles: &Possibles, idx: Index) -> Vec<Value> {
+    match data[idx] {
+        Some(value) => vec!(value),
+        None => possibles.candidates(&idx).into_iter().flatten().copied().collect()
+    }
+}
+
+// Removes the candidates of an empty cell which no digit left in `other` can pair with.
+fn restrict_pair(data: &Data, possibles: &mut Possibles, (idx, other): (Index, Index), allows: impl Fn(Value, Value) -> bool, eliminated: &mut Vec<(Index, Value)>) {
+    if data[idx].is_some() {
+        return
+    }
+    let partners = digits(data, possibles, other);
+    for value in digits(data, possibles, idx) {
+        // Neighbours share a row or column, so they never hold the same digit
+        let paired = partners.iter().any(|&partner| partner != value && allows(value, partner));
+        if !paired && possibles.update(&idx, &value) {
+            eliminated.push((idx, value));
+        }
+    }
+}
+
+impl Constraint for Dot {
+    fn name(&self) -> &str {
+        self.kind.name()
+    }
+
+    fn cells(&self) -> &[Index] {
+        &self.cells
+    }
+
+    fn allows(&self, data: &Data, idx: Index, value: Value) -> bool {
+        let other = if idx == self.cells[0] { self.cells[1] } else { self.cells[0] };
+        data[other].is_none_or(|partner| self.kind.joins(value, partner))
+    }
+
+    /// Removes the candidates of each cell with no partner left in the other.
+    fn prune(&self, data: &Data, possibles: &mut Possibles) -> Vec<(Index, Value)> {
+        let mut eliminated = vec!();
+        let [first, second] = self.cells;
+        for pair in [(first, second), (second, first)] {
+            restrict_pair(data, possibles, pair, |a, b| self.kind.joins(a, b), &mut eliminated);
+        }
+        eliminated
+    }
+}
+
+/// The absence of a dot of some kind between two neighbours, implied for the whole board: the
+/// neighbours may not fit a dot of that kind unless it is drawn.  See `Geometry::with_negative`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Negative {
+    kind: DotKind,
+    cells: Vec<Index>
+}
+
+impl Negative {
+    /// Covers every cell of the board.
+    pub fn new(kind: DotKind, geometry: &Geometry) -> Negative {
+        Negative {kind, cells: (0..geometry.cell_count()).collect()}
+    }
+
+    pub fn kind(&self) -> DotKind {
+        self.kind
+    }
+
+    // The orthogonal neighbours of a cell without a dot of this kind between them.
+    fn neighbours(&self, geometry: &Geometry, idx: Index) -> Vec<Index> {
+        let dotted: Vec<Index> = geometry.constraints_at::<Dot>(idx)
+            .filter(|dot| dot.kind == self.kind)
+            .flat_map(|dot| dot.cells)
+            .collect();
+        [(-1, 0), (0, -1), (0, 1), (1, 0)].into_iter()
+            .filter_map(|step| geometry.step(idx, step))
+            .filter(|other| !dotted.contains(other))
+            .collect()
+    }
+}
+
+impl Constraint for Negative {
+    fn name(&self) -> &str {
+        "negative"
+    }
+
+    fn cells(&self) -> &[Index] {
+        &self.cells
+    }
+
+    fn allows(&self, data: &Data, idx: Index, value: Value) -> bool {
+        self.neighbours(data.geometry(), idx).into_iter()
+            .all(|other| data[other].is_none_or(|partner| !self.kind.joins(value, partner)))
+    }
+
+    /// Removes the candidates of each empty cell with no partner left in a neighbour.
+    fn prune(&self, data: &Data, possibles: &mut Possibles) -> Vec<(Index, Value)> {
+        let mut eliminated = vec!();
+        for idx in (0..data.len()).filter(|&idx| data[idx].is_none()) {
+            for other in self.neighbours(data.geometry(), idx) {
+                restrict_pair(data, possibles, (idx, other), |a, b| !self.kind.joins(a, b), &mut eliminated);
+            }
+        }
+        eliminated
+    }
+}
+
+/// Applies a dot rule line to a geometry: `white`, `black`, `x` or `v` then two indices for a
+/// mark, `negative` then a kind when every absent mark of that kind is implied, such as
+/// `negative x`, or `non-consecutive`.  The line number is 1-based, for errors.
+pub fn parse_dot_rule(geometry: Geometry, line: &str, line_number: usize) -> Result<Geometry, SudokuError> {
+    let parse_error = |message: &str| SudokuError::Parse {line: line_number, column: 1, message: message.into()};
+    let kind = |name: &str| match name {
+        "white" => Ok(DotKind::White),
+        "black" => Ok(DotKind::Black),
+        "x" => Ok(DotKind::X),
+        "v" => Ok(DotKind::V),
+        _ => Err(parse_error("expected a `white`, `black`, `x` or `v` mark"))
+    };
+
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    match fields.as_slice() {
+        ["non-consecutive"] => Ok(geometry.with_non_consecutive()),
+        ["negative", name] => Ok(geometry.with_negative(kind(name)?)),
+        [name, first, second] => {
+            let kind = kind(name)?;
+            let (first, second) = first.parse::<Index>().ok().zip(second.parse::<Index>().ok())
+                .ok_or_else(|| parse_error("unable to parse dot cells"))?;
+            geometry.with_dot(Dot::new(first, second, kind))
+        },
+        _ => Err(parse_error("expected `<kind> <index> <index>`, `negative <kind>` or `non-consecutive`"))
+    }
+}
+
+/// Writes the dots and negative constraints of a geometry as lines, the counterpart of
+/// `parse_dot_rule`.
+pub fn write_dots(geometry: &Geometry) -> String {
+    let negative = geometry.constraints_of::<Negative>().map(|negative| format!("negative {}\n", negative.kind.name()));
+    let dots = geometry.constraints_of::<Dot>().map(|dot| format!("{} {} {}\n", dot.kind.name(), dot.first(), dot.second()));
+    negative.chain(dots).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::backtrack::first_solution;
+    use crate::constraint::remove_constraint_candidates;
+    use crate::validate;
+
+    #[test]
+    fn test_dot_pruning() {
+        let geometry = Geometry::new(3, 3).unwrap()
+            .with_dot(Dot::new(0, 1, DotKind::Black)).unwrap()
+            .with_dot(Dot::new(1, 10, DotKind::White)).unwrap();
+        let mut data = Data::with_geometry(Arc::new(geometry));
+        data[10] = Some(9);
+        let mut possibles = Possibles::from_data(&data);
+
+        remove_constraint_candidates(&data, &mut possibles);
+        assert_eq!(possibles.to_cells()[&1], vec!(8));
+        remove_constraint_candidates(&data, &mut possibles);
+        assert_eq!(possibles.to_cells()[&0], vec!(4));
+        assert_eq!(possibles.to_cells()[&2].len(), 8);
+    }
+
+    #[test]
+    fn test_negative_constraints() {
+        let geometry = Arc::new(Geometry::new(3, 3).unwrap().with_non_consecutive());
+        let solution = first_solution(&Data::with_geometry(geometry.clone())).unwrap();
+        assert!(validate(&solution).is_empty());
+        for idx in (0..81).filter(|idx| idx % 9 < 8) {
+            assert!(solution[idx].unwrap().abs_diff(solution[idx + 1].unwrap()) > 1);
+        }
+
+        let mut data = Data::with_geometry(geometry);
+        data[40] = Some(5);
+        let mut possibles = Possibles::from_data(&data);
+        remove_constraint_candidates(&data, &mut possibles);
+        assert_eq!(possibles.to_cells()[&31], vec!(1, 2, 3, 7, 8, 9));
+        assert!(possibles.to_cells()[&30].contains(&4));
+    }
+
+    #[test]
+    fn test_parse_dot_rule() {
+        let geometry = Geometry::new(3, 3).unwrap();
+        let geometry = parse_dot_rule(geometry, "black 9 0", 1).unwrap();
+        let geometry = parse_dot_rule(geometry, "negative white", 2).unwrap();
+        assert_eq!(geometry.constraints_of::<Dot>().collect::<Vec<&Dot>>(), vec!(&Dot::new(0, 9, DotKind::Black)));
+        assert_eq!(write_dots(&geometry), "negative white\nblack 0 9\n");
+        assert!(matches!(parse_dot_rule(geometry.clone(), "white 0 2", 3), Err(SudokuError::InvalidGeometry(_))));
+        assert!(matches!(parse_dot_rule(geometry, "grey 0 1", 4), Err(SudokuError::Parse {line: 4, ..})));
+    }
+
+    #[test]
+    fn test_xv() {
+        let geometry = Geometry::new(3, 3).unwrap().with_dot(Dot::new(0, 1, DotKind::X)).unwrap().with_negative(DotKind::V);
+        let mut data = Data::with_geometry(Arc::new(geometry));
+        data[0] = Some(3);
+        data[10] = Some(5);
+        let mut possibles = Possibles::from_data(&data);
+
+        remove_constraint_candidates(&data, &mut possibles);
+        assert_eq!(possibles.to_cells()[&1], vec!(7));
+        assert!(!possibles.to_cells()[&9].contains(&2));
+        assert!(possibles.to_cells()[&9].contains(&1));
+    }
+}
@@ -0,0 +1,61 @@
+use std::fmt;
+use std::io;
+
+use crate::{Index, Violation};
+
+#[derive(Debug)]
+pub enum SudokuError {
+    /// Reading the input failed
+    Io(io::Error),
+    /// Malformed input, at a 1-based line and column
+    Parse { line: usize, column: usize, message: String },
+    /// A cell index outside the board, or a band / stack outside 0..3
+    IndexOutOfRange(usize),
+    /// A digit outside 1..=size
+    DigitOutOfRange(usize),
+    /// The same cell was given more than once
+    DuplicateEntry(Index),
+    /// A permutation argument which is not a permutation
+    InvalidPermutation,
+    /// Box dimensions which don't make a supported board, or an operation which needs another size
+    InvalidGeometry(String),
+    /// The cell at this index has no remaining candidates
+    Contradiction(Index),
+    /// The givens break the rules, see `validate`
+    Invalid(Vec<Violation>),
+    Unsolvable,
+    MultipleSolutions
+}
+
+impl fmt::Display for SudokuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SudokuError::Io(err) => write!(f, "I/O error: {}", err),
+            SudokuError::Parse { line, column, message } => write!(f, "Parse error at line {}, column {}: {}", line, column, message),
+            SudokuError::IndexOutOfRange(index) => write!(f, "Index out of range: {}", index),
+            SudokuError::DigitOutOfRange(digit) => write!(f, "Digit out of range: {}", digit),
+            SudokuError::DuplicateEntry(index) => write!(f, "Duplicate entry for index {}", index),
+            SudokuError::InvalidPermutation => write!(f, "Invalid permutation"),
+            SudokuError::InvalidGeometry(message) => write!(f, "Invalid geometry: {}", message),
+            SudokuError::Contradiction(index) => write!(f, "Contradiction: no candidates left for index {}", index),
+            SudokuError::Invalid(violations) => write!(f, "Invalid puzzle: {} rule violations", violations.len()),
+            SudokuError::Unsolvable => write!(f, "Puzzle has no solution"),
+            SudokuError::MultipleSolutions => write!(f, "Puzzle has multiple solutions")
+        }
+    }
+}
+
+impl std::error::Error for SudokuError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SudokuError::Io(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for SudokuError {
+    fn from(err: io::Error) -> SudokuError {
+        SudokuError::Io(err)
+    }
+}
@@ -0,0 +1,218 @@
+use crate::{Data, Index, Value, Possibles, Solver, SudokuError};
+
+/// A solve in progress: the givens, the grid so far and the remaining candidates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub givens: Data,
+    pub data: Data,
+    pub possibles: Possibles
+}
+
+impl Snapshot {
+    /// A fresh snapshot of a puzzle, with every candidate its givens allow.
+    pub fn new(givens: Data) -> Snapshot {
+        Snapshot {data: givens.clone(), possibles: Possibles::from_data(&givens), givens}
+    }
+
+    pub fn from_solver(solver: &Solver) -> Snapshot {
+        Snapshot {givens: solver.initial_data().clone(), data: solver.data().clone(), possibles: solver.possibles().clone()}
+    }
+
+    pub fn into_solver(self) -> Result<Solver, SudokuError> {
+        Solver::from_state(self.givens, self.data, self.possibles)
+    }
+}
+
+/// Reads a Simple Sudoku `.ss` grid: one row of cells per line with `.` for blanks, with `|`
+/// between the boxes and lines of `-` between the bands.
+pub fn read_ss(content: &str) -> Result<Data, SudokuError> {
+    content.parse()
+}
+
+pub fn write_ss(data: &Data) -> String {
+    let geometry = data.geometry();
+    let size = geometry.size();
+    let mut out = String::new();
+    for row in 0..size {
+        if row != 0 && row % geometry.box_height() == 0 && !geometry.is_jigsaw() {
+            out.push_str(&"-".repeat(size + size / geometry.box_width() - 1));
+            out.push('\n');
+        }
+        for column in 0..size {
+            if column != 0 && column % geometry.box_width() == 0 && !geometry.is_jigsaw() {
+                out.push('|');
+            }
+            out.push(match data[size * row + column] {
+                Some(value) => geometry.digit_char(value),
+                None => '.'
+            });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Reads a SadMan `.sdm` list: one puzzle per line with a character per cell (81 digits for
+/// a 9×9 grid), with `0` for blanks.
+pub fn read_sdm(content: &str) -> Result<Vec<Data>, SudokuError> {
+    let mut puzzles = vec!();
+    for (n, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue
+        }
+        let data = line.parse().map_err(|err| match err {
+            SudokuError::Parse { column, message, .. } => SudokuError::Parse {line: n + 1, column, message},
+            err => err
+        })?;
+        puzzles.push(data);
+    }
+    Ok(puzzles)
+}
+
+pub fn write_sdm(puzzles: &[Data]) -> String {
+    puzzles.iter().map(|data| {
+        data.iter().map(|item| item.map_or('0', |value| data.geometry().digit_char(value))).collect::<String>() + "\n"
+    }).collect()
+}
+
+/// Reads a HoDoKu library line, `:0000:x:<grid>:<deleted candidates>:...`.
+///
+/// In the grid, digits are givens, digits following a `+` were placed while solving, and `.` or
+/// `0` are blank.  The deleted candidates are space separated `<digit><row><column>` triples
+/// (1-based), removed on top of those already ruled out by the grid.  The format is 9×9 only.
+pub fn read_hodoku(line: &str) -> Result<Snapshot, SudokuError> {
+    let parse_error = |column: usize, message: String| SudokuError::Parse {line: 1, column, message};
+
+    let fields: Vec<&str> = line.trim().split(':').collect();
+    if fields.len() < 4 || !fields[0].is_empty() {
+        return Err(parse_error(1, "expected a `:`-separated HoDoKu library line".into()));
+    }
+    let grid_column = fields[1].len() + fields[2].len() + 4;
+
+    let mut givens = Data::empty();
+    let mut data = Data::empty();
+    let mut placed = false;
+    let mut idx: Index = 0;
+    for (n, c) in fields[3].chars().enumerate() {
+        if c == '+' {
+            placed = true;
+            continue
+        }
+        if idx == 81 {
+            return Err(parse_error(grid_column + n, "more than 81 cells".into()));
+        }
+        match c {
+            '.' | '0' => {},
+            '1'..='9' => {
+                data[idx] = Some(c as u8 - b'0');
+                if !placed {
+                    givens[idx] = data[idx];
+                }
+            },
+            _ => return Err(parse_error(grid_column + n, format!("unexpected character `{}`", c)))
+        }
+        placed = false;
+        idx += 1;
+    }
+    if idx != 81 {
+        return Err(parse_error(grid_column, format!("expected 81 cells, found {}", idx)));
+    }
+
+    let mut possibles = Possibles::from_data(&data);
+    if let Some(deleted) = fields.get(4) {
+        let deleted_column = grid_column + fields[3].len() + 1;
+        for token in deleted.split_whitespace() {
+            let (value, idx) = parse_candidate(token)
+                .ok_or_else(|| parse_error(deleted_column, format!("invalid candidate `{}`", token)))?;
+            possibles.update(&idx, &value);
+        }
+    }
+
+    Ok(Snapshot {givens, data, possibles})
+}
+
+// A `<digit><row><column>` triple, with 1-based row and column.
+fn parse_candidate(token: &str) -> Option<(Value, Index)> {
+    let digits: Vec<u32> = token.chars().map(|c| c.to_digit(10)).collect::<Option<Vec<u32>>>()?;
+    match digits[..] {
+        [value, row, column] if value > 0 && row > 0 && column > 0 => {
+            Some((value as Value, 9 * (row as Index - 1) + column as Index - 1))
+        },
+        _ => None
+    }
+}
+
+/// Writes a HoDoKu library line, the counterpart of `read_hodoku`, failing for grids other
+/// than 9×9.
+pub fn write_hodoku(snapshot: &Snapshot) -> Result<String, SudokuError> {
+    if !snapshot.data.geometry().is_classic() {
+        return Err(SudokuError::InvalidGeometry("HoDoKu lines hold classic 9x9 grids only".into()));
+    }
+
+    let mut grid = String::new();
+    for idx in 0..81 {
+        match snapshot.data[idx] {
+            Some(value) => {
+                if snapshot.givens[idx].is_none() {
+                    grid.push('+');
+                }
+                grid.push((b'0' + value) as char);
+            },
+            None => grid.push('.')
+        }
+    }
+
+    let base = Possibles::from_data(&snapshot.data);
+    let mut deleted = vec!();
+    for idx in (0..81).filter(|&i| snapshot.data[i].is_none()) {
+        let remaining = snapshot.possibles.candidates(&idx);
+        for value in 1..10 {
+            let possible = base.candidates(&idx).is_some_and(|c| c.contains(&value));
+            if possible && !remaining.is_some_and(|c| c.contains(&value)) {
+                deleted.push(format!("{}{}{}", value, idx / 9 + 1, idx % 9 + 1));
+            }
+        }
+    }
+
+    Ok(format!(":0000:x:{}:{}::", grid, deleted.join(" ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUZZLE: &str = "1....7.9..3..2...8..96..5....53..9...1..8...26....4...3......1..4......7..7...3..";
+
+    #[test]
+    fn test_ss_and_sdm() {
+        let data: Data = PUZZLE.parse().unwrap();
+        let ss = write_ss(&data);
+
+        assert!(ss.starts_with("1..|..7|.9.\n.3.|.2.|..8\n..9|6..|5..\n-----------\n"));
+        assert_eq!(read_ss(&ss).unwrap(), data);
+
+        let sdm = write_sdm(&[data.clone(), Data::empty()]);
+        assert!(sdm.starts_with("100007090030020008"));
+        assert_eq!(read_sdm(&sdm).unwrap(), vec!(data, Data::empty()));
+
+        let small: Data = "1.3..2..3..2.4..".parse().unwrap();
+        assert_eq!(write_ss(&small), "1.|3.\n.2|..\n-----\n3.|.2\n.4|..\n");
+        assert_eq!(read_ss(&write_ss(&small)).unwrap(), small);
+        assert!(matches!(read_sdm("123\n"), Err(SudokuError::Parse {line: 1, ..})));
+    }
+
+    #[test]
+    fn test_hodoku_round_trip() {
+        let mut snapshot = Snapshot::new(PUZZLE.parse().unwrap());
+        snapshot.data[1] = Some(2);
+        snapshot.possibles = Possibles::from_data(&snapshot.data);
+        snapshot.possibles.update(&2, &4);
+        snapshot.possibles.update(&80, &9);
+        let line = write_hodoku(&snapshot).unwrap();
+
+        assert_eq!(line, format!(":0000:x:1+2{}:413 999::", &PUZZLE[2..]));
+        assert_eq!(read_hodoku(&line).unwrap(), snapshot);
+        assert!(read_hodoku(":0000:x:123::").is_err());
+    }
+}
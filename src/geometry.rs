@@ -0,0 +1,752 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::RangeInclusive;
+use std::sync::{Arc, OnceLock};
+
+use crate::{Index, Value, Position, SudokuError};
+use crate::comparison::{Inequality, Parity};
+use crate::constraint::Constraint;
+use crate::dots::{Dot, DotKind, Negative};
+use crate::killer::{Cage, max_sum};
+use crate::lines::Line;
+
+/// The kinds of window each digit must appear in exactly once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum Unit {
+    Row,
+    Column,
+    Subsquare,
+    /// An irregular jigsaw region, in place of the subsquares
+    Region,
+    /// One of the two main diagonals, numbered from the top left (Sudoku-X)
+    Diagonal,
+    /// An extra box-shaped window between the subsquares (Windoku, or Hyper Sudoku)
+    Window,
+    /// Any other extra unit declared with `Geometry::with_unit`
+    Extra
+}
+
+/// Global rules keeping a digit from repeating a chess move away, on top of the units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum Relation {
+    /// Cells a knight's move apart
+    AntiKnight,
+    /// Touching cells, diagonals included
+    AntiKing
+}
+
+impl Relation {
+    /// The row and column steps from a cell to the cells it relates to.
+    pub fn offsets(&self) -> &'static [(isize, isize)] {
+        match self {
+            Relation::AntiKnight => &[(-2, -1), (-2, 1), (-1, -2), (-1, 2), (1, -2), (1, 2), (2, -1), (2, 1)],
+            Relation::AntiKing => &[(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)]
+        }
+    }
+}
+
+/// The largest supported side length, with digits written `1`-`9` then `A`-`P`.
+pub const MAX_SIZE: usize = 25;
+
+/// The shape of the board: a square of `size` rows and columns, split into boxes of
+/// `box_width` columns by `box_height` rows, holding the digits 1..=size.
+///
+/// Cells are indexed from the top left, counting rows to the right.  The index windows
+/// (rows, columns and subsquares) and the peers of each cell are worked out once, up front.
+///
+/// A jigsaw geometry replaces the subsquares with irregular regions, see `Geometry::jigsaw`.
+/// It keeps the box dimensions usual for its size, which only shape the candidate layout.
+///
+/// Puzzles such as Samurai are made of several grids overlapping on whole boxes, see
+/// `Geometry::overlapping`.  The grids sit on a shared canvas, and cells are indexed in
+/// reading order across it, skipping the gaps, so a single grid keeps the usual indexing.
+///
+/// Together the units make up the rules of the board, and variants can declare extra units
+/// on top of the rows, columns and subsquares, such as the diagonals of Sudoku-X.  Peers and
+/// every strategy follow from the units, so they respect the extra ones too.
+///
+/// Relations such as anti-knight add peers outside the units, see `Geometry::with_relation`.
+///
+/// Every other rule is a `Constraint` added with `Geometry::with_constraint`, so a puzzle is the
+/// board shape plus its list of constraints.  Killer cages, Kropki dots, lines, outside clues,
+/// odd/even cells and greater-than signs are constraints too, with builders such as
+/// `Geometry::with_cage` which check them against the board first.  The cells of a cage, or
+/// of any constraint whose digits must differ, are peers.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Geometry {
+    box_width: usize,
+    box_height: usize,
+    size: usize,
+    grids: Vec<(usize, usize)>,
+    width: usize,
+    height: usize,
+    positions: Vec<(usize, usize)>,
+    canvas: Vec<Option<Index>>,
+    regions: Vec<Index>,
+    jigsaw: bool,
+    units: Vec<(Unit, Index, Vec<Index>)>,
+    relations: Vec<Relation>,
+    constraints: Constraints,
+    cell_units: Vec<Vec<usize>>,
+    cell_constraints: Vec<Vec<usize>>,
+    related: Vec<Vec<Index>>,
+    peers: Vec<Vec<Index>>
+}
+
+// The constraints added to a geometry, which compare and hash by identity, so a geometry equals
+// its clones.
+#[derive(Clone, Default)]
+struct Constraints(Vec<Arc<dyn Constraint>>);
+
+impl PartialEq for Constraints {
+    fn eq(&self, other: &Constraints) -> bool {
+        self.0.len() == other.0.len() && self.0.iter().zip(other.0.iter()).all(|(a, b)| Arc::ptr_eq(a, b))
+    }
+}
+
+impl Eq for Constraints {}
+
+impl Hash for Constraints {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for constraint in self.0.iter() {
+            (Arc::as_ptr(constraint) as *const ()).hash(state);
+        }
+    }
+}
+
+// As squarely as possible, and wider than tall.
+fn box_shape(size: usize) -> (usize, usize) {
+    let box_height = (1..=size).take_while(|h| h * h <= size).filter(|h| size.is_multiple_of(*h)).last().unwrap_or(1);
+    (size / box_height, box_height)
+}
+
+impl Geometry {
+    pub fn new(box_width: usize, box_height: usize) -> Result<Geometry, SudokuError> {
+        let size = box_width * box_height;
+        if box_width == 0 || box_height == 0 || !(2..=MAX_SIZE).contains(&size) {
+            return Err(SudokuError::InvalidGeometry(format!(
+                "{}x{} boxes give a side outside 2..={}", box_width, box_height, MAX_SIZE
+            )));
+        }
+
+        Ok(Geometry::build(box_width, box_height, vec!((0, 0)), None))
+    }
+
+    /// Several grids with boxes of the same shape, overlapping on whole boxes, where `grids`
+    /// gives the top left corner of each on the canvas as a row and column.  A box shared by
+    /// grids is a single unit, so deductions carry from one grid to the next through it.
+    pub fn overlapping(box_width: usize, box_height: usize, grids: Vec<(usize, usize)>) -> Result<Geometry, SudokuError> {
+        let single = Geometry::new(box_width, box_height)?;
+        let mut distinct = grids.clone();
+        distinct.sort();
+        distinct.dedup();
+        if grids.is_empty() || distinct.len() != grids.len() {
+            return Err(SudokuError::InvalidGeometry("the grids need distinct corners".into()));
+        }
+        if let Some(&(row, column)) = grids.iter().find(|(row, column)| row % box_height != 0 || column % box_width != 0) {
+            return Err(SudokuError::InvalidGeometry(format!("the grid at {},{} does not line up with the boxes", row, column)));
+        }
+        Ok(Geometry::build(single.box_width, single.box_height, grids, None))
+    }
+
+    /// Five 9×9 grids, with the one in the middle sharing a corner box with each of the others.
+    pub fn samurai() -> Geometry {
+        Geometry::overlapping(3, 3, vec!((0, 0), (0, 12), (6, 6), (12, 0), (12, 12))).unwrap()
+    }
+
+    /// Two 9×9 grids sharing a corner box.
+    pub fn twin() -> Geometry {
+        Geometry::overlapping(3, 3, vec!((0, 0), (6, 6))).unwrap()
+    }
+
+    /// Four 9×9 grids in a 12×12 square, each shifted a box from the next.
+    pub fn butterfly() -> Geometry {
+        Geometry::overlapping(3, 3, vec!((0, 0), (0, 3), (3, 0), (3, 3))).unwrap()
+    }
+
+    /// The overlapping layouts by name: `samurai`, `twin` or `butterfly`.
+    pub fn layout(name: &str) -> Option<Geometry> {
+        match name {
+            "samurai" => Some(Geometry::samurai()),
+            "twin" => Some(Geometry::twin()),
+            "butterfly" => Some(Geometry::butterfly()),
+            _ => None
+        }
+    }
+
+    /// A jigsaw geometry, where `regions` gives the region (numbered from 0) of each cell in
+    /// index order.  There must be as many regions as rows, each with as many cells as a row.
+    pub fn jigsaw(regions: Vec<Index>) -> Result<Geometry, SudokuError> {
+        let size = (2..=MAX_SIZE).find(|s| s * s == regions.len()).ok_or_else(|| {
+            SudokuError::InvalidGeometry(format!("{} cells do not make a square board", regions.len()))
+        })?;
+
+        let mut counts = vec!(0; size);
+        for &region in regions.iter() {
+            if region >= size {
+                return Err(SudokuError::InvalidGeometry(format!("region {} outside 0..{}", region, size)));
+            }
+            counts[region] += 1;
+        }
+        if let Some(region) = counts.iter().position(|&count| count != size) {
+            return Err(SudokuError::InvalidGeometry(format!(
+                "region {} has {} cells instead of {}", region, counts[region], size
+            )));
+        }
+
+        let (box_width, box_height) = box_shape(size);
+        Ok(Geometry::build(box_width, box_height, vec!((0, 0)), Some(regions)))
+    }
+
+    // Lays the grids out on the canvas, then numbers the rows and columns grid by grid and the
+    // boxes (unless jigsaw regions are given) in reading order.
+    fn build(box_width: usize, box_height: usize, grids: Vec<(usize, usize)>, regions: Option<Vec<Index>>) -> Geometry {
+        let size = box_width * box_height;
+        let height = grids.iter().map(|&(row, _)| row + size).max().unwrap();
+        let width = grids.iter().map(|&(_, column)| column + size).max().unwrap();
+
+        let covered = |row: usize, column: usize| grids.iter()
+            .any(|&(top, left)| (top..top + size).contains(&row) && (left..left + size).contains(&column));
+        let positions: Vec<(usize, usize)> = (0..height)
+            .flat_map(|row| (0..width).map(move |column| (row, column)))
+            .filter(|&(row, column)| covered(row, column))
+            .collect();
+        let mut canvas = vec!(None; width * height);
+        for (idx, &(row, column)) in positions.iter().enumerate() {
+            canvas[width * row + column] = Some(idx);
+        }
+        let cell = |row: usize, column: usize| canvas[width * row + column].unwrap();
+
+        let jigsaw = regions.is_some();
+        let regions = regions.unwrap_or_else(|| {
+            let boxes: Vec<(usize, usize)> = positions.iter().map(|&(row, column)| (row / box_height, column / box_width)).collect();
+            let mut order = boxes.clone();
+            order.sort();
+            order.dedup();
+            boxes.iter().map(|b| order.binary_search(b).unwrap()).collect()
+        });
+
+        let mut units: Vec<(Unit, Index, Vec<Index>)> = vec!();
+        for (grid, &(top, left)) in grids.iter().enumerate() {
+            for row in 0..size {
+                units.push((Unit::Row, size * grid + row, (0..size).map(|column| cell(top + row, left + column)).collect()));
+            }
+        }
+        for (grid, &(top, left)) in grids.iter().enumerate() {
+            for column in 0..size {
+                units.push((Unit::Column, size * grid + column, (0..size).map(|row| cell(top + row, left + column)).collect()));
+            }
+        }
+        let kind = if jigsaw { Unit::Region } else { Unit::Subsquare };
+        for region in 0..regions.iter().max().unwrap() + 1 {
+            units.push((kind, region, (0..positions.len()).filter(|&idx| regions[idx] == region).collect()));
+        }
+
+        let mut geometry = Geometry {
+            box_width, box_height, size, grids, width, height, positions, canvas,
+            regions, jigsaw, units, relations: vec!(), constraints: Constraints::default(),
+            cell_units: vec!(), cell_constraints: vec!(), related: vec!(), peers: vec!()
+        };
+        geometry.link();
+        geometry
+    }
+
+    // Works out the units and constraints of each cell, and its peers, from the list of units,
+    // relations and constraints.
+    fn link(&mut self) {
+        let cell_count = self.cell_count();
+        self.related = (0..cell_count).map(|idx| {
+            let mut related: Vec<Index> = self.relations.iter()
+                .flat_map(|relation| relation.offsets().iter().filter_map(|&step| self.step(idx, step)))
+                .collect();
+            related.sort();
+            related.dedup();
+            related
+        }).collect();
+
+        let mut cell_units = vec!(vec!(); cell_count);
+        for (n, (_, _, idxs)) in self.units.iter().enumerate() {
+            for &idx in idxs {
+                cell_units[idx].push(n);
+            }
+        }
+        let mut cell_constraints = vec!(vec!(); cell_count);
+        for (n, constraint) in self.constraints.0.iter().enumerate() {
+            for &idx in constraint.cells() {
+                cell_constraints[idx].push(n);
+            }
+        }
+
+        self.peers = (0..cell_count).map(|idx| {
+            let mut peers: Vec<Index> = cell_units[idx].iter()
+                .flat_map(|&n: &usize| self.units[n].2.iter().copied())
+                .chain(self.related[idx].iter().copied())
+                .chain(cell_constraints[idx].iter()
+                    .map(|&n| &self.constraints.0[n])
+                    .filter(|constraint| constraint.is_distinct())
+                    .flat_map(|constraint| constraint.cells().iter().copied()))
+                .filter(|&i| i != idx)
+                .collect();
+            peers.sort();
+            peers.dedup();
+            peers
+        }).collect();
+        self.cell_units = cell_units;
+        self.cell_constraints = cell_constraints;
+    }
+
+    /// Adds an extra unit, which must hold every digit once, failing unless it has as many
+    /// distinct cells as a row.  The unit is numbered after the others of the same kind.
+    pub fn with_unit(mut self, unit: Unit, idxs: Vec<Index>) -> Result<Geometry, SudokuError> {
+        if matches!(unit, Unit::Row | Unit::Column | Unit::Subsquare | Unit::Region) {
+            return Err(SudokuError::InvalidGeometry(format!("{:?} units are part of every board", unit)));
+        }
+        let mut sorted = idxs.clone();
+        sorted.sort();
+        sorted.dedup();
+        if sorted.len() != self.size || idxs.iter().any(|&idx| idx >= self.cell_count()) {
+            return Err(SudokuError::InvalidGeometry(format!("an extra unit needs {} distinct cells on the board", self.size)));
+        }
+
+        let index = self.units.iter().filter(|(kind, _, _)| *kind == unit).count();
+        self.units.push((unit, index, idxs));
+        self.link();
+        Ok(self)
+    }
+
+    /// Adds a killer cage, failing if it leaves the board or overlaps another cage.
+    pub fn with_cage(self, cage: Cage) -> Result<Geometry, SudokuError> {
+        if cage.cells().len() > self.size || cage.sum() > max_sum(cage.cells().len(), self.size) {
+            return Err(SudokuError::InvalidGeometry(format!("a {}-cell cage cannot add up to {}", cage.cells().len(), cage.sum())));
+        }
+        if let Some(&idx) = cage.cells().iter().find(|idx| self.constraints_of::<Cage>().any(|other| other.cells().contains(idx))) {
+            return Err(SudokuError::InvalidGeometry(format!("cell {} is in two cages", idx)));
+        }
+        self.with_constraint(cage)
+    }
+
+    /// Adds a relation, so that cells that move apart become peers.
+    pub fn with_relation(mut self, relation: Relation) -> Geometry {
+        if !self.relations.contains(&relation) {
+            self.relations.push(relation);
+            self.link();
+        }
+        self
+    }
+
+    pub fn relations(&self) -> &[Relation] {
+        &self.relations
+    }
+
+    /// The cells tied to `idx` by the relations alone, in index order.
+    pub fn related(&self, idx: Index) -> &[Index] {
+        &self.related[idx]
+    }
+
+    /// The cell `rows` down and `columns` across from `idx`, if that stays on the board.
+    pub fn step(&self, idx: Index, (rows, columns): (isize, isize)) -> Option<Index> {
+        let (row, column) = self.positions[idx];
+        let row = row.checked_add_signed(rows).filter(|&row| row < self.height)?;
+        let column = column.checked_add_signed(columns).filter(|&column| column < self.width)?;
+        self.index_at(row, column)
+    }
+
+    /// Adds a Kropki dot, failing unless it joins two orthogonal neighbours without a dot yet.
+    pub fn with_dot(self, dot: Dot) -> Result<Geometry, SudokuError> {
+        let (first, second) = (dot.first(), dot.second());
+        if second >= self.cell_count() {
+            return Err(SudokuError::IndexOutOfRange(second));
+        }
+        if ![(0, 1), (1, 0)].into_iter().any(|step| self.step(first, step) == Some(second)) {
+            return Err(SudokuError::InvalidGeometry(format!("cells {} and {} are not neighbours", first, second)));
+        }
+        if self.constraints_of::<Dot>().any(|other| other.cells() == dot.cells()) {
+            return Err(SudokuError::InvalidGeometry(format!("cells {} and {} already have a dot", first, second)));
+        }
+        self.with_constraint(dot)
+    }
+
+    /// Implies every absent dot of a kind: neighbours without a white dot may not be
+    /// consecutive, or without a black dot may not be in a 1:2 ratio.
+    pub fn with_negative(self, kind: DotKind) -> Geometry {
+        if self.constraints_of::<Negative>().any(|negative| negative.kind() == kind) {
+            return self
+        }
+        let negative = Negative::new(kind, &self);
+        self.with_constraint(negative).unwrap()
+    }
+
+    /// Keeps orthogonal neighbours from holding consecutive digits, the same rule as implying
+    /// the absent white dots on a board without any.
+    pub fn with_non_consecutive(self) -> Geometry {
+        self.with_negative(DotKind::White)
+    }
+
+    /// Adds a line, failing unless it runs through at least two distinct cells, each touching
+    /// the next.  Thermometers and renban lines can be no longer than a row.
+    pub fn with_line(self, line: Line) -> Result<Geometry, SudokuError> {
+        let cells = line.cells();
+        if let Some(&idx) = cells.iter().find(|&&idx| idx >= self.cell_count()) {
+            return Err(SudokuError::IndexOutOfRange(idx));
+        }
+        let mut distinct = cells.to_vec();
+        distinct.sort();
+        distinct.dedup();
+        if cells.len() < 2 || distinct.len() != cells.len() || (line.kind().is_distinct() && cells.len() > self.size) {
+            return Err(SudokuError::InvalidGeometry(format!("a {} line cannot run through {} cells", line.kind().name(), cells.len())));
+        }
+        let touching = |(a, b): (Index, Index)| Relation::AntiKing.offsets().iter().any(|&step| self.step(a, step) == Some(b));
+        if let Some(pair) = cells.windows(2).find(|pair| !touching((pair[0], pair[1]))) {
+            return Err(SudokuError::InvalidGeometry(format!("cells {} and {} do not touch", pair[0], pair[1])));
+        }
+        self.with_constraint(line)
+    }
+
+    /// Shades a cell odd or even, failing if it is already shaded the other way.
+    pub fn with_parity(self, parity: Parity) -> Result<Geometry, SudokuError> {
+        let shading = self.constraints_of::<Parity>().find(|shaded| shaded.cell() == parity.cell()).map(|shaded| shaded.kind());
+        match shading {
+            Some(kind) if kind != parity.kind() => {
+                Err(SudokuError::InvalidGeometry(format!("cell {} is already {}", parity.cell(), kind.name())))
+            },
+            Some(_) => Ok(self),
+            None => self.with_constraint(parity)
+        }
+    }
+
+    /// Adds a greater-than sign, failing unless it joins two orthogonal neighbours without a
+    /// sign yet.
+    pub fn with_inequality(self, inequality: Inequality) -> Result<Geometry, SudokuError> {
+        let (greater, lesser) = (inequality.greater(), inequality.lesser());
+        if let Some(idx) = [greater, lesser].into_iter().find(|&idx| idx >= self.cell_count()) {
+            return Err(SudokuError::IndexOutOfRange(idx));
+        }
+        if ![(-1, 0), (0, -1), (0, 1), (1, 0)].into_iter().any(|step| self.step(greater, step) == Some(lesser)) {
+            return Err(SudokuError::InvalidGeometry(format!("cells {} and {} are not neighbours", greater, lesser)));
+        }
+        if self.constraints_of::<Inequality>().any(|other| other.cells().contains(&greater) && other.cells().contains(&lesser)) {
+            return Err(SudokuError::InvalidGeometry(format!("cells {} and {} already have a sign", greater, lesser)));
+        }
+        self.with_constraint(inequality)
+    }
+
+    /// Adds a constraint, failing unless its cells are on the board, and no more than a row
+    /// when they must be distinct.  The cells of a distinct constraint become peers.
+    pub fn with_constraint(mut self, constraint: impl Constraint) -> Result<Geometry, SudokuError> {
+        if let Some(&idx) = constraint.cells().iter().find(|&&idx| idx >= self.cell_count()) {
+            return Err(SudokuError::IndexOutOfRange(idx));
+        }
+        if constraint.is_distinct() && constraint.cells().len() > self.size {
+            return Err(SudokuError::InvalidGeometry(format!("no {} digits are distinct", constraint.cells().len())));
+        }
+
+        self.constraints.0.push(Arc::new(constraint));
+        self.link();
+        Ok(self)
+    }
+
+    /// The rules of the board beyond its units and relations, such as cages, dots and lines, in
+    /// the order they were added.  `Violation::Constraint` refers to them by position.
+    pub fn constraints(&self) -> &[Arc<dyn Constraint>] {
+        &self.constraints.0
+    }
+
+    /// The constraints of one type, such as every `Cage`, in the order they were added.
+    pub fn constraints_of<T: Constraint>(&self) -> impl Iterator<Item = &T> {
+        self.constraints.0.iter().filter_map(|constraint| constraint.downcast_ref::<T>())
+    }
+
+    /// The constraints of one type applying to a cell, such as the dots joining it to its
+    /// neighbours.
+    pub fn constraints_at<T: Constraint>(&self, idx: Index) -> impl Iterator<Item = &T> {
+        self.cell_constraints[idx].iter().filter_map(|&n| self.constraints.0[n].downcast_ref::<T>())
+    }
+
+    // The positions in `constraints()` of the constraints applying to a cell.
+    pub(crate) fn cell_constraints(&self, idx: Index) -> &[usize] {
+        &self.cell_constraints[idx]
+    }
+
+    /// Adds the two main diagonals of each grid as units, for Sudoku-X.
+    pub fn with_diagonals(mut self) -> Geometry {
+        let size = self.size;
+        for (top, left) in self.grids.clone() {
+            let diagonal = (0..size).map(|k| self.index_at(top + k, left + k).unwrap()).collect();
+            let anti_diagonal = (0..size).map(|k| self.index_at(top + k, left + size - 1 - k).unwrap()).collect();
+            self = self.with_unit(Unit::Diagonal, diagonal).unwrap()
+                .with_unit(Unit::Diagonal, anti_diagonal).unwrap();
+        }
+        self
+    }
+
+    /// Adds box-shaped windows set one cell in from each box corner and spaced a cell apart,
+    /// the four extra units of Windoku on a 9×9 grid, to each grid.
+    pub fn with_windows(mut self) -> Geometry {
+        let (width, height, size) = (self.box_width, self.box_height, self.size);
+        for (grid_top, grid_left) in self.grids.clone() {
+            let tops = (1..size).step_by(height + 1).filter(|top| top + height < size);
+            for top in tops {
+                for left in (1..size).step_by(width + 1).filter(|left| left + width < size) {
+                    let idxs = (0..size)
+                        .map(|k| self.index_at(grid_top + top + k / width, grid_left + left + k % width).unwrap())
+                        .collect();
+                    self = self.with_unit(Unit::Window, idxs).unwrap();
+                }
+            }
+        }
+        self
+    }
+
+    /// The standard 9×9 board with 3×3 boxes, shared between every grid that uses it.
+    pub fn classic() -> Arc<Geometry> {
+        static CLASSIC: OnceLock<Arc<Geometry>> = OnceLock::new();
+        CLASSIC.get_or_init(|| Arc::new(Geometry::new(3, 3).unwrap())).clone()
+    }
+
+    /// The usual box shape for a side length: as square as possible, and wider than tall,
+    /// so 6 gives 3×2 boxes and 12 gives 4×3.
+    pub fn for_size(size: usize) -> Result<Arc<Geometry>, SudokuError> {
+        if size == 9 {
+            return Ok(Geometry::classic());
+        }
+        let (box_width, box_height) = box_shape(size);
+        Geometry::new(box_width, box_height).map(Arc::new)
+    }
+
+    /// True for the standard 9×9 board, which the transformations and some file formats need.
+    pub fn is_classic(&self) -> bool {
+        *self == *Geometry::classic()
+    }
+
+    /// True when the subsquares are replaced with irregular regions.
+    pub fn is_jigsaw(&self) -> bool {
+        self.jigsaw
+    }
+
+    /// The subsquare or jigsaw region of each cell, in index order.
+    pub fn regions(&self) -> &[Index] {
+        &self.regions
+    }
+
+    /// The geometry of a grid with `count` cells, when `count` is the square of a supported side.
+    pub fn from_cell_count(count: usize) -> Option<Arc<Geometry>> {
+        let size = (1..=MAX_SIZE).find(|s| s * s == count)?;
+        Geometry::for_size(size).ok()
+    }
+
+    pub fn box_width(&self) -> usize {
+        self.box_width
+    }
+
+    pub fn box_height(&self) -> usize {
+        self.box_height
+    }
+
+    /// The number of rows and columns, which is also the largest digit.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// The top left corner of each grid on the canvas, as a row and column.  A single grid sits
+    /// at the origin.
+    pub fn grids(&self) -> &[(usize, usize)] {
+        &self.grids
+    }
+
+    /// The number of columns of the canvas, which is `size` for a single grid.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The number of rows of the canvas, which is `size` for a single grid.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The cell at a row and column of the canvas, if it is not in a gap between grids.
+    pub fn index_at(&self, row: usize, column: usize) -> Option<Index> {
+        if row < self.height && column < self.width { self.canvas[self.width * row + column] } else { None }
+    }
+
+    /// The digits a cell may hold.
+    pub fn values(&self) -> RangeInclusive<Value> {
+        1..=self.size as Value
+    }
+
+    pub fn row(&self, row: Index) -> &[Index] {
+        self.unit(Unit::Row, row)
+    }
+
+    pub fn column(&self, column: Index) -> &[Index] {
+        self.unit(Unit::Column, column)
+    }
+
+    /// The cells of a subsquare, or of a region on a jigsaw board.
+    pub fn subsquare(&self, subsquare: Index) -> &[Index] {
+        self.unit(if self.jigsaw { Unit::Region } else { Unit::Subsquare }, subsquare)
+    }
+
+    /// The cells of a unit, or none if the board has no such unit.
+    pub fn unit(&self, unit: Unit, index: Index) -> &[Index] {
+        self.units.iter()
+            .find(|(kind, n, _)| *kind == unit && *n == index)
+            .map_or(&[], |(_, _, idxs)| idxs.as_slice())
+    }
+
+    /// Every unit of the board with its kind and number: rows, then columns, then subsquares
+    /// (or regions), then any extra units.  Rows and columns are numbered grid by grid, so row
+    /// `size * g + r` is row `r` of grid `g`.
+    pub fn units(&self) -> impl Iterator<Item = (Unit, Index, &[Index])> {
+        self.units.iter().map(|(unit, index, idxs)| (*unit, *index, idxs.as_slice()))
+    }
+
+    // The positions in `units()` of the units containing a cell.
+    pub(crate) fn cell_units(&self, idx: Index) -> &[usize] {
+        &self.cell_units[idx]
+    }
+
+    /// The cells sharing a unit or a constraint of distinct digits, such as a cage, with `idx`,
+    /// or related to it, in index order, not including `idx` itself.
+    pub fn around(&self, idx: Index) -> &[Index] {
+        &self.peers[idx]
+    }
+
+    /// The row and column of a cell on the canvas, with its box.
+    pub fn position_from_index(&self, idx: Index) -> Position {
+        let (row, column) = self.positions[idx];
+        Position {row, column, subsquare: self.regions[idx]}
+    }
+
+    /// The character for a digit: `1`-`9`, then `A` for 10 up to `P` for 25.
+    pub fn digit_char(&self, value: Value) -> char {
+        match value {
+            1..=9 => (b'0' + value) as char,
+            _ => (b'A' + value - 10) as char
+        }
+    }
+
+    /// Reads a digit written by `digit_char`, in either case, if it fits the board.
+    pub fn parse_digit(&self, c: char) -> Option<Value> {
+        let value = match c {
+            '1'..='9' => c as u8 - b'0',
+            'A'..='P' => c as u8 - b'A' + 10,
+            'a'..='p' => c as u8 - b'a' + 10,
+            _ => return None
+        };
+        self.values().contains(&value).then_some(value)
+    }
+}
+
+// The unit and peer tables follow from the box shape and grids, so they are left out.
+impl fmt::Debug for Geometry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Geometry")
+            .field("box_width", &self.box_width)
+            .field("box_height", &self.box_height)
+            .field("jigsaw", &self.jigsaw)
+            .field("grids", &self.grids)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classic() {
+        let geometry = Geometry::classic();
+
+        assert_eq!(geometry.row(1), &[9, 10, 11, 12, 13, 14, 15, 16, 17]);
+        assert_eq!(geometry.column(2), &[2, 11, 20, 29, 38, 47, 56, 65, 74]);
+        assert_eq!(geometry.subsquare(4), &[30, 31, 32, 39, 40, 41, 48, 49, 50]);
+        assert_eq!(geometry.around(0).len(), 20);
+        assert_eq!(geometry.position_from_index(80).subsquare, 8);
+    }
+
+    #[test]
+    fn test_rectangular_boxes() {
+        let geometry = Geometry::for_size(6).unwrap();
+        assert_eq!((geometry.box_width(), geometry.box_height()), (3, 2));
+        assert_eq!(geometry.subsquare(1), &[3, 4, 5, 9, 10, 11]);
+        assert_eq!(geometry.subsquare(2), &[12, 13, 14, 18, 19, 20]);
+        assert_eq!(geometry.position_from_index(21).subsquare, 3);
+
+        let geometry = Geometry::for_size(16).unwrap();
+        assert_eq!(geometry.parse_digit('G'), Some(16));
+        assert_eq!(geometry.parse_digit('H'), None);
+        assert_eq!(geometry.digit_char(12), 'C');
+        assert!(Geometry::new(5, 6).is_err());
+        assert!(Geometry::from_cell_count(80).is_none());
+    }
+
+    #[test]
+    fn test_extra_units() {
+        let geometry = Geometry::new(3, 3).unwrap().with_diagonals().with_windows();
+
+        assert_eq!(geometry.unit(Unit::Diagonal, 0), &[0, 10, 20, 30, 40, 50, 60, 70, 80]);
+        assert_eq!(geometry.unit(Unit::Diagonal, 1), &[8, 16, 24, 32, 40, 48, 56, 64, 72]);
+        assert_eq!(geometry.unit(Unit::Window, 3), &[50, 51, 52, 59, 60, 61, 68, 69, 70]);
+        assert_eq!(geometry.units().count(), 33);
+        assert_eq!(geometry.around(40).len(), 20 + 12);
+        assert_eq!(geometry.around(10).len(), 20 + 6 + 2);
+        assert!(!geometry.is_classic());
+        assert!(Geometry::new(2, 2).unwrap().with_unit(Unit::Extra, vec!(0, 1, 2, 2)).is_err());
+    }
+
+    #[test]
+    fn test_jigsaw() {
+        let regions = vec!(0, 0, 1, 1, 0, 2, 2, 1, 0, 2, 3, 1, 2, 3, 3, 3);
+        let geometry = Geometry::jigsaw(regions).unwrap();
+
+        assert_eq!(geometry.unit(Unit::Region, 2), &[5, 6, 9, 12]);
+        assert_eq!(geometry.position_from_index(9).subsquare, 2);
+        assert_eq!(geometry.around(0), &[1, 2, 3, 4, 8, 12]);
+        assert!(!geometry.is_classic());
+        assert!(Geometry::jigsaw(vec!(0, 0, 1, 1, 0, 2, 2, 1, 0, 2, 3, 1, 2, 3, 3, 2)).is_err());
+    }
+
+    #[test]
+    fn test_relations() {
+        let geometry = Geometry::new(3, 3).unwrap().with_relation(Relation::AntiKnight);
+        assert_eq!(geometry.related(0), &[11, 19]);
+        assert_eq!(geometry.related(40).len(), 8);
+        assert_eq!(geometry.around(40).len(), 20 + 8);
+
+        let geometry = geometry.with_relation(Relation::AntiKing).with_relation(Relation::AntiKing);
+        assert_eq!(geometry.relations(), &[Relation::AntiKnight, Relation::AntiKing]);
+        assert_eq!(geometry.related(0), &[1, 9, 10, 11, 19]);
+        assert_eq!(geometry.around(2).len(), 20 + 3);
+        assert_eq!(geometry.step(8, (0, 1)), None);
+    }
+
+    #[test]
+    fn test_overlapping() {
+        let geometry = Geometry::samurai();
+        assert_eq!(geometry.cell_count(), 5 * 81 - 4 * 9);
+        assert_eq!((geometry.width(), geometry.height()), (21, 21));
+        assert_eq!(geometry.units().filter(|(unit, _, _)| *unit == Unit::Subsquare).count(), 5 * 9 - 4);
+        assert_eq!(geometry.index_at(0, 9), None);
+        assert_eq!(geometry.index_at(0, 12), Some(9));
+        assert_eq!(geometry.row(9), &(9..18).collect::<Vec<Index>>()[..]);
+
+        // A cell of a shared box sees the rows and columns of both grids
+        let shared = geometry.index_at(6, 6).unwrap();
+        assert_eq!(geometry.around(shared).len(), 14 + 14 + 4);
+        assert_eq!(geometry.step(geometry.index_at(2, 8).unwrap(), (0, 1)), None);
+        let position = geometry.position_from_index(shared);
+        assert_eq!((position.row(), position.column()), (6, 6));
+
+        assert_eq!(Geometry::twin().cell_count(), 2 * 81 - 9);
+        assert_eq!(Geometry::butterfly().cell_count(), 144);
+        assert!(Geometry::layout("pentagon").is_none());
+        assert!(Geometry::overlapping(3, 3, vec!((0, 0), (0, 4))).is_err());
+        assert!(Geometry::overlapping(3, 3, vec!((0, 0), (0, 0))).is_err());
+    }
+}
@@ -0,0 +1,349 @@
+use std::collections::HashSet;
+
+use crate::{Constraint, Data, Geometry, Index, Value, Possibles, SudokuError, Unit};
+
+/// A killer cage: a group of cells whose digits don't repeat and add up to `sum`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Cage {
+    cells: Vec<Index>,
+    sum: usize
+}
+
+impl Cage {
+    /// Fails unless the cells are distinct and some set of distinct digits up to `size` makes the sum.
+    pub fn new(cells: Vec<Index>, sum: usize, size: usize) -> Result<Cage, SudokuError> {
+        let distinct: HashSet<Index> = HashSet::from_iter(cells.iter().copied());
+        if cells.is_empty() || distinct.len() != cells.len() || cells.len() > size {
+            return Err(SudokuError::InvalidGeometry(format!("a cage needs 1 to {} distinct cells", size)));
+        }
+        if !(min_sum(cells.len())..=max_sum(cells.len(), size)).contains(&sum) {
+            return Err(SudokuError::InvalidGeometry(format!("no {} distinct digits add up to {}", cells.len(), sum)));
+        }
+        Ok(Cage {cells, sum})
+    }
+
+    pub fn sum(&self) -> usize {
+        self.sum
+    }
+}
+
+// The smallest and largest sums of `count` distinct digits from 1..=size.
+pub(crate) fn min_sum(count: usize) -> usize {
+    count * (count + 1) / 2
+}
+
+pub(crate) fn max_sum(count: usize, size: usize) -> usize {
+    count * size - count * count.saturating_sub(1) / 2
+}
+
+/// Every set of `count` distinct digits from 1..=size adding up to `sum`, as bit masks with
+/// bit `d` set for digit `d`, skipping digits set in `excluded`.
+pub fn combinations(sum: usize, count: usize, size: usize, excluded: u32) -> Vec<u32> {
+    fn extend(out: &mut Vec<u32>, from: usize, sum: usize, count: usize, size: usize, excluded: u32, mask: u32) {
+        if count == 0 {
+            if sum == 0 {
+                out.push(mask);
+            }
+            return
+        }
+        for digit in from..=size.min(sum) {
+            if excluded & (1 << digit) == 0 {
+                extend(out, digit + 1, sum - digit, count - 1, size, excluded, mask | 1 << digit);
+            }
+        }
+    }
+
+    let mut out = vec!();
+    extend(&mut out, 1, sum, count, size, excluded, 0);
+    out
+}
+
+// The candidates of a cell as a bit mask.
+fn candidate_mask(possibles: &Possibles, idx: Index) -> u32 {
+    possibles.candidates(&idx).into_iter().flatten().fold(0, |acc, value| acc | 1 << value)
+}
+
+// True when each cell can take a different digit of `digits`, by finding augmenting paths.
+fn has_matching(cells: &[u32], digits: u32) -> bool {
+    fn augment(cell: usize, cells: &[u32], digits: u32, owner: &mut [Option<usize>; 32], seen: &mut u32) -> bool {
+        for digit in 0..32 {
+            let bit = 1 << digit;
+            if cells[cell] & digits & bit == 0 || *seen & bit != 0 {
+                continue
+            }
+            *seen |= bit;
+            let free = match owner[digit] {
+                None => true,
+                Some(other) => augment(other, cells, digits, owner, seen)
+            };
+            if free {
+                owner[digit] = Some(cell);
+                return true
+            }
+        }
+        false
+    }
+
+    let mut owner = [None; 32];
+    (0..cells.len()).all(|cell| augment(cell, cells, digits, &mut owner, &mut 0))
+}
+
+/// The digits of a cage don't repeat, so its cells are peers, and add up to its sum.
+impl Constraint for Cage {
+    fn name(&self) -> &str {
+        "cage"
+    }
+
+    fn cells(&self) -> &[Index] {
+        &self.cells
+    }
+
+    fn is_distinct(&self) -> bool {
+        true
+    }
+
+    /// The rest of the cage must still be able to make up what the digit leaves of the sum.
+    fn allows(&self, data: &Data, idx: Index, value: Value) -> bool {
+        let others: Vec<Value> = self.cells.iter().filter(|&&cell| cell != idx).filter_map(|&cell| data[cell]).collect();
+        let empty = self.cells.len() - 1 - others.len();
+        let sum = others.iter().map(|&other| other as usize).sum::<usize>() + value as usize;
+        let reachable = self.sum.checked_sub(sum)
+            .is_some_and(|left| (min_sum(empty)..=max_sum(empty, data.geometry().size())).contains(&left));
+        !others.contains(&value) && reachable
+    }
+
+    /// Removes the candidates which appear in no combination of digits that makes the cage
+    /// sum, given the digits already placed in the cage.
+    fn prune(&self, data: &Data, possibles: &mut Possibles) -> Vec<(Index, Value)> {
+        let geometry = data.geometry();
+        let mut eliminated = vec!();
+
+        let empty: Vec<Index> = self.cells.iter().copied().filter(|&idx| data[idx].is_none()).collect();
+        if empty.is_empty() {
+            return eliminated
+        }
+        let placed: Vec<Value> = self.cells.iter().filter_map(|&idx| data[idx]).collect();
+        let placed_mask = placed.iter().fold(0, |acc, &value| acc | 1 << value);
+        let placed_sum: usize = placed.iter().map(|&value| value as usize).sum();
+
+        let masks: Vec<u32> = empty.iter().map(|&idx| candidate_mask(possibles, idx)).collect();
+        let allowed = self.sum.checked_sub(placed_sum).map_or(0, |remaining| {
+            combinations(remaining, empty.len(), geometry.size(), placed_mask).into_iter()
+                .filter(|&combination| has_matching(&masks, combination))
+                .fold(0, |acc, combination| acc | combination)
+        });
+
+        for (&idx, &mask) in empty.iter().zip(masks.iter()) {
+            for value in geometry.values().filter(|&value| mask & !allowed & (1 << value) != 0) {
+                if possibles.update(&idx, &value) {
+                    eliminated.push((idx, value));
+                }
+            }
+        }
+        eliminated
+    }
+}
+
+// Narrows the candidates of a group of cells known to add up to `sum`, not necessarily with
+// distinct digits, to those within reach of the smallest and largest totals of the others.
+pub(crate) fn restrict_sum(data: &Data, possibles: &mut Possibles, cells: &[Index], sum: usize, eliminated: &mut Vec<(Index, Value)>) {
+    let bounds: Vec<(usize, usize)> = cells.iter().map(|&idx| match data[idx] {
+        Some(value) => (value as usize, value as usize),
+        None => {
+            let candidates = possibles.candidates(&idx).into_iter().flatten();
+            candidates.fold((usize::MAX, 0), |(lo, hi), &value| (lo.min(value as usize), hi.max(value as usize)))
+        }
+    }).collect();
+    if bounds.iter().any(|&(lo, _)| lo == usize::MAX) {
+        return
+    }
+    let (lo_total, hi_total) = bounds.iter().fold((0, 0), |(lo, hi), bound| (lo + bound.0, hi + bound.1));
+
+    for (&idx, &(lo, hi)) in cells.iter().zip(bounds.iter()) {
+        if data[idx].is_some() {
+            continue
+        }
+        let (others_lo, others_hi) = (lo_total - lo, hi_total - hi);
+        let candidates: Vec<Value> = possibles.candidates(&idx).into_iter().flatten().copied().collect();
+        for value in candidates {
+            let total = value as usize;
+            if total + others_lo > sum || total + others_hi < sum {
+                possibles.update(&idx, &value);
+                eliminated.push((idx, value));
+            }
+        }
+    }
+}
+
+// Groups of cells known to hold each digit a fixed number of times: every unit, and every
+// run of consecutive rows or columns within a grid.
+fn groups(geometry: &Geometry) -> Vec<(Vec<Index>, usize)> {
+    let size = geometry.size();
+    let mut groups: Vec<(Vec<Index>, usize)> = geometry.units().map(|(_, _, idxs)| (idxs.to_vec(), 1)).collect();
+    for unit in [Unit::Row, Unit::Column] {
+        for first in (0..geometry.grids().len()).map(|grid| size * grid) {
+            for start in first..first + size {
+                for end in start + 2..=first + size {
+                    let idxs = (start..end).flat_map(|line| geometry.unit(unit, line).iter().copied()).collect();
+                    groups.push((idxs, end - start));
+                }
+            }
+        }
+    }
+    groups
+}
+
+/// Applies the rule of 45 (for 9×9) to every unit and run of consecutive rows or columns.
+///
+/// The cages lying wholly inside a group leave the rest of its cells (the innies) to make up
+/// the group total.  When the cages crossing the group cover it, the cells of those cages
+/// sticking out of it (the outies) add up to the difference.  Either sum bounds the candidates
+/// of those cells.  Returns the `(index, value)` candidates eliminated.
+pub fn remove_innies_outies(data: &Data, possibles: &mut Possibles) -> Vec<(Index, Value)> {
+    let geometry = data.geometry();
+    let unit_total = geometry.size() * (geometry.size() + 1) / 2;
+    let mut eliminated = vec!();
+
+    for (idxs, lines) in groups(geometry) {
+        let group: HashSet<Index> = HashSet::from_iter(idxs.iter().copied());
+        let total = lines * unit_total;
+
+        let (inside, crossing): (Vec<&Cage>, Vec<&Cage>) = geometry.constraints_of::<Cage>()
+            .filter(|cage| cage.cells().iter().any(|idx| group.contains(idx)))
+            .partition(|cage| cage.cells().iter().all(|idx| group.contains(idx)));
+        let inside_sum: usize = inside.iter().map(|cage| cage.sum()).sum();
+        if inside_sum > total {
+            continue
+        }
+
+        let covered: HashSet<Index> = inside.iter().flat_map(|cage| cage.cells().iter().copied()).collect();
+        let innies: Vec<Index> = idxs.iter().copied().filter(|idx| !covered.contains(idx)).collect();
+        if !innies.is_empty() && !inside.is_empty() {
+            restrict_sum(data, possibles, &innies, total - inside_sum, &mut eliminated);
+        }
+
+        let crossing_cells: HashSet<Index> = crossing.iter().flat_map(|cage| cage.cells().iter().copied()).collect();
+        if !crossing.is_empty() && innies.iter().all(|idx| crossing_cells.contains(idx)) {
+            let crossing_sum: usize = crossing.iter().map(|cage| cage.sum()).sum();
+            let mut outies: Vec<Index> = crossing_cells.into_iter().filter(|idx| !group.contains(idx)).collect();
+            outies.sort();
+            if let Some(sum) = (inside_sum + crossing_sum).checked_sub(total) {
+                restrict_sum(data, possibles, &outies, sum, &mut eliminated);
+            }
+        }
+    }
+    eliminated
+}
+
+/// Reads a `cage <sum> <index> <index> ...` line, with the 1-based line number for errors.
+pub fn parse_cage(line: &str, line_number: usize, size: usize) -> Result<Cage, SudokuError> {
+    let parse_error = |message: &str| SudokuError::Parse {line: line_number, column: 1, message: message.into()};
+
+    let mut fields = line.split_whitespace();
+    if fields.next() != Some("cage") {
+        return Err(parse_error("expected `cage <sum> <index> <index> ...`"));
+    }
+    let sum = fields.next().and_then(|sum| sum.parse::<usize>().ok())
+        .ok_or_else(|| parse_error("unable to parse cage sum"))?;
+    let cells = fields.map(|idx| idx.parse::<Index>()).collect::<Result<Vec<Index>, _>>()
+        .map_err(|_| parse_error("unable to parse cage cell"))?;
+    Cage::new(cells, sum, size)
+}
+
+/// Writes the cages of a geometry as `cage` lines, the counterpart of `parse_cage`.
+pub fn write_cages(geometry: &Geometry) -> String {
+    geometry.constraints_of::<Cage>().map(|cage| {
+        let cells: Vec<String> = cage.cells().iter().map(|idx| idx.to_string()).collect();
+        format!("cage {} {}\n", cage.sum(), cells.join(" "))
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::Solver;
+    use crate::backtrack::first_solution;
+    use crate::constraint::remove_constraint_candidates;
+
+    const SOLUTION: &str = "315892647794163528268754391471685932952371864836429715547236189629518473183947256";
+
+    #[test]
+    fn test_combinations() {
+        assert_eq!(combinations(3, 2, 9, 0), vec!(0b110));
+        assert_eq!(combinations(10, 2, 9, 0).len(), 4);
+        assert_eq!(combinations(10, 2, 9, 1 << 1), vec!(1 << 2 | 1 << 8, 1 << 3 | 1 << 7, 1 << 4 | 1 << 6));
+        assert_eq!(combinations(45, 9, 9, 0).len(), 1);
+        assert!(combinations(18, 2, 9, 0).is_empty());
+    }
+
+    #[test]
+    fn test_cage_pruning() {
+        let geometry = Geometry::new(3, 3).unwrap()
+            .with_cage(Cage::new(vec!(0, 1), 3, 9).unwrap()).unwrap()
+            .with_cage(Cage::new(vec!(2, 3, 4), 24, 9).unwrap()).unwrap()
+            .with_cage(Cage::new(vec!(5, 6, 7), 13, 9).unwrap()).unwrap();
+        let data = Data::with_geometry(Arc::new(geometry));
+        let mut possibles = Possibles::from_data(&data);
+
+        remove_constraint_candidates(&data, &mut possibles);
+        assert_eq!(possibles.to_cells()[&0], vec!(1, 2));
+        assert_eq!(possibles.to_cells()[&3], vec!(7, 8, 9));
+
+        // Row 0 holds 45, which leaves 45 - 3 - 24 - 13 for its last cell
+        remove_innies_outies(&data, &mut possibles);
+        assert_eq!(possibles.to_cells()[&8], vec!(5));
+        assert_eq!(possibles.to_cells()[&9].len(), 9);
+    }
+
+    #[test]
+    fn test_innies_bottom_band() {
+        // Dominoes down the columns of the last two rows, which no single row holds, and a
+        // lone cell at 71 leave cell 80 to the innies of those two rows
+        let solution: Data = SOLUTION.parse().unwrap();
+        let mut geometry = Geometry::new(3, 3).unwrap()
+            .with_cage(Cage::new(vec!(71), solution[71].unwrap() as usize, 9).unwrap()).unwrap();
+        for top in 63..71 {
+            let sum = (solution[top].unwrap() + solution[top + 9].unwrap()) as usize;
+            geometry = geometry.with_cage(Cage::new(vec!(top, top + 9), sum, 9).unwrap()).unwrap();
+        }
+        let data = Data::with_geometry(Arc::new(geometry));
+        let mut possibles = Possibles::from_data(&data);
+
+        remove_innies_outies(&data, &mut possibles);
+        assert_eq!(possibles.to_cells()[&80], vec!(solution[80].unwrap()));
+    }
+
+    #[test]
+    fn test_solve_killer() {
+        // Four dominoes in each row, leaving the last column to the innies
+        let solution: Data = SOLUTION.parse().unwrap();
+        let mut geometry = Geometry::new(3, 3).unwrap();
+        for start in (0..81).filter(|idx| idx % 9 < 8 && idx % 2 == (idx / 9) % 2) {
+            let sum = (solution[start].unwrap() + solution[start + 1].unwrap()) as usize;
+            geometry = geometry.with_cage(Cage::new(vec!(start, start + 1), sum, 9).unwrap()).unwrap();
+        }
+        let data = Data::with_geometry(Arc::new(geometry));
+
+        let mut solver = Solver::new(data.clone()).unwrap();
+        solver.solve().unwrap();
+        for idx in (8..81).step_by(9) {
+            assert_eq!(solver.data()[idx], solution[idx]);
+        }
+
+        let found = first_solution(&data).unwrap();
+        for cage in data.geometry().constraints_of::<Cage>() {
+            assert_eq!(cage.cells().iter().map(|&idx| found[idx].unwrap() as usize).sum::<usize>(), cage.sum());
+        }
+    }
+
+    #[test]
+    fn test_parse_cage() {
+        let cage = parse_cage("cage 15 0 1 9", 1, 9).unwrap();
+        assert_eq!((cage.cells(), cage.sum()), (&[0, 1, 9][..], 15));
+        assert!(parse_cage("cage 2 0 1", 3, 9).is_err());
+        assert!(matches!(parse_cage("cage x 0", 3, 9), Err(SudokuError::Parse {line: 3, ..})));
+    }
+}
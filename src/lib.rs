@@ -1,21 +1,72 @@
 pub type Index = usize;
 pub type Value = u8;
 pub type DataCell = Option<Value>;
-pub type Data = [DataCell;81];
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Position {
     row: Index,
     column: Index,
     subsquare: Index
 }
 
+impl Position {
+    pub fn row(&self) -> Index {
+        self.row
+    }
 
-mod indices;
+    pub fn column(&self) -> Index {
+        self.column
+    }
+
+    pub fn subsquare(&self) -> Index {
+        self.subsquare
+    }
+}
+
+
+mod data;
+mod error;
+mod geometry;
 mod possibles;
+mod random;
+mod rating;
+mod report;
 mod solver;
+mod validate;
+pub mod backtrack;
+pub mod batch;
+pub mod clues;
+pub mod collection;
+pub mod comparison;
+pub mod constraint;
+pub mod dots;
+pub mod formats;
+pub mod killer;
+pub mod lines;
+pub mod minimal;
+pub mod pencilmarks;
+pub mod regions;
+pub mod svg;
+pub mod transform;
 pub mod utils;
 
-pub use crate::indices::Indices;
+pub use crate::clues::{Clue, ClueKind};
+pub use crate::comparison::{Inequality, Parity, ParityKind};
+pub use crate::constraint::Constraint;
+pub use crate::data::Data;
+pub use crate::dots::{Dot, DotKind, Negative};
+pub use crate::error::SudokuError;
+pub use crate::geometry::{Geometry, MAX_SIZE, Relation, Unit};
+pub use crate::killer::Cage;
+pub use crate::lines::{Line, LineKind};
 pub use crate::possibles::Possibles;
-pub use crate::solver::Solver;
\ No newline at end of file
+pub use crate::rating::{Rating, rate, rate_with};
+pub use crate::report::{FORMAT_VERSION, SolveReport, Status, write_progress};
+pub use crate::solver::{Pass, Solver, Step, Strategy};
+pub use crate::validate::{Violation, validate};
+pub use crate::minimal::{Symmetry, generate, is_minimal, minimize, redundant_givens};
+pub use crate::transform::{
+    Axis, Transformation, are_equivalent, minlex, permute_columns, permute_rows, random_equivalent, reflect, relabel,
+    rotate, swap_bands, swap_stacks, transpose
+};
\ No newline at end of file
@@ -0,0 +1,322 @@
+use crate::{Constraint, Data, Geometry, Index, Value, Possibles, SudokuError};
+
+/// The rules a line drawn through the grid can carry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum LineKind {
+    /// The digits strictly increase from the bulb, the first cell
+    Thermometer,
+    /// The digit in the circle, the first cell, is the sum of the digits along the arrow
+    Arrow,
+    /// Neighbouring digits along the line differ by at least 5 (German whispers)
+    Whisper,
+    /// The digits form a set of consecutive digits, in any order
+    Renban,
+    /// The digits read the same in both directions
+    Palindrome
+}
+
+impl LineKind {
+    /// The keyword for the line in puzzle files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            LineKind::Thermometer => "thermo",
+            LineKind::Arrow => "arrow",
+            LineKind::Whisper => "whisper",
+            LineKind::Renban => "renban",
+            LineKind::Palindrome => "palindrome"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<LineKind> {
+        [LineKind::Thermometer, LineKind::Arrow, LineKind::Whisper, LineKind::Renban, LineKind::Palindrome]
+            .into_iter()
+            .find(|kind| kind.name() == name)
+    }
+
+    /// True when no digit can repeat along the line, which makes its cells peers.
+    pub fn is_distinct(&self) -> bool {
+        matches!(self, LineKind::Thermometer | LineKind::Renban)
+    }
+}
+
+/// A line through a path of cells, each touching the next, diagonals included.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Line {
+    kind: LineKind,
+    cells: Vec<Index>
+}
+
+impl Line {
+    pub fn new(kind: LineKind, cells: Vec<Index>) -> Line {
+        Line {kind, cells}
+    }
+
+    pub fn kind(&self) -> LineKind {
+        self.kind
+    }
+
+    // True when `value` in the cell at `position` along the line keeps to the rule, given the
+    // digits placed in the other cells of the line.
+    fn keeps(&self, position: usize, value: Value, data: &Data) -> bool {
+        let size = data.geometry().size();
+        let placed = |n: usize| if n == position { Some(value) } else { data[self.cells[n]] };
+        let value = value as usize;
+
+        match self.kind {
+            LineKind::Thermometer => {
+                let room = value > position && value + self.cells.len() - 1 - position <= size;
+                room && (0..self.cells.len()).filter(|&n| n != position).all(|n| placed(n).is_none_or(|other| {
+                    let other = other as usize;
+                    if n < position { value >= other + position - n } else { value + n - position <= other }
+                }))
+            },
+            LineKind::Arrow => {
+                let empty = (1..self.cells.len()).filter(|&n| placed(n).is_none()).count();
+                let sum: usize = (1..self.cells.len()).filter_map(placed).map(|other| other as usize).sum();
+                match placed(0) {
+                    Some(circle) if empty == 0 => sum == circle as usize,
+                    Some(circle) => sum + empty <= circle as usize,
+                    None => sum + empty <= size
+                }
+            },
+            LineKind::Whisper => [position.checked_sub(1), Some(position + 1)].into_iter().flatten()
+                .filter(|&n| n < self.cells.len())
+                .all(|n| data[self.cells[n]].is_none_or(|other| value.abs_diff(other as usize) >= 5)),
+            LineKind::Renban => {
+                let digits: Vec<usize> = (0..self.cells.len()).filter_map(placed).map(|other| other as usize).collect();
+                let (lowest, highest) = (digits.iter().min().unwrap(), digits.iter().max().unwrap());
+                highest - lowest < self.cells.len()
+            },
+            LineKind::Palindrome => {
+                let mirror = self.cells.len() - 1 - position;
+                data[self.cells[mirror]].is_none_or(|other| mirror == position || other as usize == value)
+            }
+        }
+    }
+}
+
+// The digits a cell may still hold: its own if placed, otherwise its candidates, in order.
+fn digits(data: &Data, possibles: &Possibles, idx: Index) -> Vec<Value> {
+    match data[idx] {
+        Some(value) => vec!(value),
+        None => {
+            let mut candidates: Vec<Value> = possibles.candidates(&idx).into_iter().flatten().copied().collect();
+            candidates.sort();
+            candidates
+        }
+    }
+}
+
+// Removes the candidates of an empty cell outside `keep`, recording them.
+fn retain(data: &Data, possibles: &mut Possibles, idx: Index, keep: impl Fn(Value) -> bool, eliminated: &mut Vec<(Index, Value)>) {
+    if data[idx].is_some() {
+        return
+    }
+    for value in digits(data, possibles, idx).into_iter().filter(|&value| !keep(value)) {
+        if possibles.update(&idx, &value) {
+            eliminated.push((idx, value));
+        }
+    }
+}
+
+// The smallest and largest digit left to a cell, or None when it has no candidates.
+fn bounds(data: &Data, possibles: &Possibles, idx: Index) -> Option<(usize, usize)> {
+    let digits = digits(data, possibles, idx);
+    Some((*digits.first()? as usize, *digits.last()? as usize))
+}
+
+fn restrict_thermometer(data: &Data, possibles: &mut Possibles, cells: &[Index], eliminated: &mut Vec<(Index, Value)>) {
+    let Some(bounds) = cells.iter().map(|&idx| bounds(data, possibles, idx)).collect::<Option<Vec<(usize, usize)>>>() else {
+        return
+    };
+
+    // Each cell is above the lowest digit of the cell before it, and below the highest after it
+    let mut lows = vec!(0; cells.len());
+    let mut highs = vec!(0; cells.len());
+    for n in 0..cells.len() {
+        lows[n] = if n == 0 { bounds[n].0 } else { bounds[n].0.max(lows[n - 1] + 1) };
+    }
+    for n in (0..cells.len()).rev() {
+        highs[n] = if n + 1 == cells.len() { bounds[n].1 } else { bounds[n].1.min(highs[n + 1].saturating_sub(1)) };
+    }
+    for (n, &idx) in cells.iter().enumerate() {
+        retain(data, possibles, idx, |value| (lows[n]..=highs[n]).contains(&(value as usize)), eliminated);
+    }
+}
+
+fn restrict_arrow(data: &Data, possibles: &mut Possibles, cells: &[Index], eliminated: &mut Vec<(Index, Value)>) {
+    let Some(bounds) = cells.iter().map(|&idx| bounds(data, possibles, idx)).collect::<Option<Vec<(usize, usize)>>>() else {
+        return
+    };
+    let (circle_low, circle_high) = bounds[0];
+    let low: usize = bounds[1..].iter().map(|bound| bound.0).sum();
+    let high: usize = bounds[1..].iter().map(|bound| bound.1).sum();
+
+    retain(data, possibles, cells[0], |value| (low..=high).contains(&(value as usize)), eliminated);
+    for (n, &idx) in cells.iter().enumerate().skip(1) {
+        let (others_low, others_high) = (low - bounds[n].0, high - bounds[n].1);
+        retain(data, possibles, idx, |value| {
+            let value = value as usize;
+            value + others_low <= circle_high && value + others_high >= circle_low
+        }, eliminated);
+    }
+}
+
+// Keeps the candidates of each of a pair of cells which some digit left in the other allows.
+fn restrict_pair(data: &Data, possibles: &mut Possibles, pair: (Index, Index), allows: impl Fn(Value, Value) -> bool, eliminated: &mut Vec<(Index, Value)>) {
+    for (idx, other) in [pair, (pair.1, pair.0)] {
+        let partners = digits(data, possibles, other);
+        retain(data, possibles, idx, |value| partners.iter().any(|&partner| allows(value, partner)), eliminated);
+    }
+}
+
+fn restrict_renban(data: &Data, possibles: &mut Possibles, cells: &[Index], size: usize, eliminated: &mut Vec<(Index, Value)>) {
+    let length = cells.len();
+    let masks: Vec<u32> = cells.iter()
+        .map(|&idx| digits(data, possibles, idx).iter().fold(0, |acc, &value| acc | 1 << value))
+        .collect();
+
+    // The runs of consecutive digits which every cell can still take part in
+    let allowed = (1..=(size + 1).saturating_sub(length)).fold(0u32, |acc, start| {
+        let run = (start..start + length).fold(0u32, |acc, value| acc | 1 << value);
+        if masks.iter().all(|mask| mask & run != 0) { acc | run } else { acc }
+    });
+    for &idx in cells {
+        retain(data, possibles, idx, |value| allowed & (1 << value) != 0, eliminated);
+    }
+}
+
+impl Constraint for Line {
+    fn name(&self) -> &str {
+        self.kind.name()
+    }
+
+    fn cells(&self) -> &[Index] {
+        &self.cells
+    }
+
+    fn is_distinct(&self) -> bool {
+        self.kind.is_distinct()
+    }
+
+    fn allows(&self, data: &Data, idx: Index, value: Value) -> bool {
+        self.cells.iter().position(|&cell| cell == idx).is_none_or(|position| self.keeps(position, value, data))
+    }
+
+    /// Narrows the candidates along the line to the digits its rule leaves in reach: bounds
+    /// along thermometers and arrows, partners along whispers and palindromes, and runs of
+    /// consecutive digits along renban lines.
+    fn prune(&self, data: &Data, possibles: &mut Possibles) -> Vec<(Index, Value)> {
+        let cells = &self.cells;
+        let mut eliminated = vec!();
+        match self.kind {
+            LineKind::Thermometer => restrict_thermometer(data, possibles, cells, &mut eliminated),
+            LineKind::Arrow => restrict_arrow(data, possibles, cells, &mut eliminated),
+            LineKind::Whisper => for pair in cells.windows(2) {
+                restrict_pair(data, possibles, (pair[0], pair[1]), |a, b| a.abs_diff(b) >= 5, &mut eliminated);
+            },
+            LineKind::Renban => restrict_renban(data, possibles, cells, data.geometry().size(), &mut eliminated),
+            LineKind::Palindrome => for n in 0..cells.len() / 2 {
+                let pair = (cells[n], cells[cells.len() - 1 - n]);
+                restrict_pair(data, possibles, pair, |a, b| a == b, &mut eliminated);
+            }
+        }
+        eliminated
+    }
+}
+
+/// Reads a line rule such as `thermo <index> <index> ...`, with the keyword of its kind
+/// first, onto a geometry.  The line number is 1-based, for errors.
+pub fn parse_line_rule(geometry: Geometry, line: &str, line_number: usize) -> Result<Geometry, SudokuError> {
+    let parse_error = |message: &str| SudokuError::Parse {line: line_number, column: 1, message: message.into()};
+
+    let mut fields = line.split_whitespace();
+    let kind = fields.next().and_then(LineKind::from_name)
+        .ok_or_else(|| parse_error("expected `thermo`, `arrow`, `whisper`, `renban` or `palindrome`"))?;
+    let cells = fields.map(|idx| idx.parse::<Index>()).collect::<Result<Vec<Index>, _>>()
+        .map_err(|_| parse_error("unable to parse line cell"))?;
+    geometry.with_line(Line::new(kind, cells))
+}
+
+/// Writes the lines of a geometry as rule lines, the counterpart of `parse_line_rule`.
+pub fn write_lines(geometry: &Geometry) -> String {
+    geometry.constraints_of::<Line>().map(|line| {
+        let cells: Vec<String> = line.cells().iter().map(|idx| idx.to_string()).collect();
+        format!("{} {}\n", line.kind().name(), cells.join(" "))
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::validate;
+    use crate::backtrack::first_solution;
+    use crate::constraint::remove_constraint_candidates;
+
+    fn lined(lines: &[(LineKind, &[Index])]) -> Data {
+        let geometry = lines.iter().fold(Geometry::new(3, 3).unwrap(), |geometry, (kind, cells)| {
+            geometry.with_line(Line::new(*kind, cells.to_vec())).unwrap()
+        });
+        Data::with_geometry(Arc::new(geometry))
+    }
+
+    #[test]
+    fn test_thermometer_and_arrow() {
+        let mut data = lined(&[(LineKind::Thermometer, &[0, 1, 2, 11]), (LineKind::Arrow, &[40, 41, 42])]);
+        data[42] = Some(7);
+        let mut possibles = Possibles::from_data(&data);
+
+        remove_constraint_candidates(&data, &mut possibles);
+        assert_eq!(possibles.to_cells()[&0], vec!(1, 2, 3, 4, 5, 6));
+        assert_eq!(possibles.to_cells()[&11], vec!(4, 5, 6, 7, 8, 9));
+        assert_eq!(possibles.to_cells()[&40], vec!(8, 9));
+        assert_eq!(possibles.to_cells()[&41], vec!(1, 2));
+    }
+
+    #[test]
+    fn test_whisper_renban_palindrome() {
+        let mut data = lined(&[
+            (LineKind::Whisper, &[0, 1, 2]),
+            (LineKind::Renban, &[27, 28, 29]),
+            (LineKind::Palindrome, &[45, 55, 65])
+        ]);
+        data[27] = Some(9);
+        data[65] = Some(3);
+        let mut possibles = Possibles::from_data(&data);
+
+        // Cell 0 sees the 9 below it, which leaves 4 without a partner too
+        remove_constraint_candidates(&data, &mut possibles);
+        assert_eq!(possibles.to_cells()[&1], vec!(1, 2, 3, 6, 7, 8, 9));
+        assert_eq!(possibles.to_cells()[&28], vec!(7, 8));
+        assert_eq!(possibles.to_cells()[&45], vec!(3));
+        assert!(possibles.to_cells()[&55].contains(&5));
+    }
+
+    #[test]
+    fn test_solve_with_lines() {
+        let data = lined(&[
+            (LineKind::Thermometer, &[0, 10, 20, 30]),
+            (LineKind::Arrow, &[8, 16, 24]),
+            (LineKind::Whisper, &[72, 73, 74]),
+            (LineKind::Renban, &[44, 53, 62]),
+            (LineKind::Palindrome, &[36, 46, 56])
+        ]);
+        let solution = first_solution(&data).unwrap();
+        assert!(validate(&solution).is_empty());
+        assert!(solution[0] < solution[10] && solution[20] < solution[30]);
+        assert_eq!(solution[8].unwrap(), solution[16].unwrap() + solution[24].unwrap());
+        assert_eq!(solution[36], solution[56]);
+    }
+
+    #[test]
+    fn test_parse_line_rule() {
+        let geometry = parse_line_rule(Geometry::new(3, 3).unwrap(), "arrow 0 10 20", 1).unwrap();
+        assert_eq!(write_lines(&geometry), "arrow 0 10 20\n");
+        assert!(matches!(parse_line_rule(geometry.clone(), "thermo 0 2", 2), Err(SudokuError::InvalidGeometry(_))));
+        assert!(matches!(parse_line_rule(geometry, "snake 0 1", 3), Err(SudokuError::Parse {line: 3, ..})));
+    }
+}
@@ -1,29 +1,639 @@
-
 use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 use sudoku_rs::{
+    Data,
+    Geometry,
+    Rating,
+    Relation,
+    SolveReport,
     Solver,
+    Status,
+    Step,
+    Strategy,
+    SudokuError,
+    Symmetry,
+    batch::{Throughput, run_batch, write_results},
+    backtrack::{count_solutions, solve_unique},
+    collection::parse_collection,
+    formats::{Snapshot, read_hodoku, write_ss},
+    generate,
+    pencilmarks::{parse_candidates, parse_grid, write_grid},
+    regions::read_regions,
     utils::{
-        parse_from_file,
-        print_puzzle
-    }
+        parse_from_str,
+        parse_with_geometry,
+        write_puzzle
+    },
+    validate,
+    write_progress
 };
 
+// The exit codes scripts can rely on
+const EXIT_SOLVED: i32 = 0;
+const EXIT_ERROR: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_INVALID: i32 = 3;
+const EXIT_UNSOLVABLE: i32 = 4;
+const EXIT_MULTIPLE: i32 = 5;
+// `hint` found no logical step with the strategies allowed
+const EXIT_STUCK: i32 = 6;
+
+const USAGE: &str = "\
+Usage: sudoku-rs [<command>] [<puzzle_path>] [options]
+
+Commands:
+  solve      Solve the puzzle and print the solution (the default)
+  validate   Check the givens against the rules and for a unique solution
+  rate       Print how hard the puzzle is: easy, medium, hard or fiendish
+  hint       Print the next logical step
+  generate   Generate a new puzzle
+  convert    Read the puzzle and write it in another format
+  batch      Solve a collection, one puzzle per line, writing tab separated results
+
+The puzzle is read from stdin when the path is missing or `-`.  Only the requested output goes
+to stdout, or to the --output file; messages go to stderr.
+
+Options:
+  --input auto|pencilmarks|candidates|hodoku
+  --format grid|line|ss|pencilmarks|json   (json needs the serde feature)
+  --strategies <name>,...     e.g. naked-single,hidden-single
+  --output <path>             batch also takes the results path after the collection path
+  --quiet, --verbose
+  --regions <region_path>, --diagonals, --windows, --anti-knight, --anti-king, --non-consecutive
+  generate: --size <width>x<height> | --layout <name>, --symmetry <name>, --seed <number>
+  batch: --threads <count>
+
+Exit codes: 0 solved, 1 error, 2 usage, 3 invalid input, 4 unsolvable, 5 multiple solutions,
+  6 no hint: the strategies find no next step";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Solve,
+    Validate,
+    Rate,
+    Hint,
+    Generate,
+    Convert,
+    Batch
+}
+
+impl Command {
+    fn from_name(name: &str) -> Option<Command> {
+        match name {
+            "solve" => Some(Command::Solve),
+            "validate" => Some(Command::Validate),
+            "rate" => Some(Command::Rate),
+            "hint" => Some(Command::Hint),
+            "generate" => Some(Command::Generate),
+            "convert" => Some(Command::Convert),
+            // The flag came before the subcommands
+            "batch" | "--batch" => Some(Command::Batch),
+            _ => None
+        }
+    }
+}
+
+/// How the puzzle input is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InputFormat {
+    /// A grid, an 81 character line or index-value lines, with any rule lines
+    Auto,
+    /// A pencilmark grid, see `pencilmarks::write_grid`
+    Pencilmarks,
+    /// A candidate list, see `pencilmarks::write_candidates`
+    Candidates,
+    /// A HoDoKu line
+    Hodoku
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Grid,
+    Line,
+    Ss,
+    /// A pencilmark grid, with the candidates of any open cells
+    Pencilmarks,
+    /// A `SolveReport` from `solve`, `validate` and `rate`, a step from `hint` and the grid
+    /// string otherwise
+    Json
+}
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Options {
+    command: Command,
+    path: Option<String>,
+    output: Option<String>,
+    input: InputFormat,
+    format: OutputFormat,
+    strategies: Vec<Strategy>,
+    verbosity: Verbosity,
+    regions: Option<String>,
+    diagonals: bool,
+    windows: bool,
+    relations: Vec<Relation>,
+    non_consecutive: bool,
+    size: Option<(usize, usize)>,
+    layout: Option<String>,
+    symmetry: Symmetry,
+    seed: Option<u64>,
+    threads: Option<usize>
+}
+
+impl Options {
+    fn new(command: Command) -> Options {
+        Options {
+            command,
+            path: None,
+            output: None,
+            input: InputFormat::Auto,
+            format: OutputFormat::Grid,
+            strategies: Strategy::ALL.to_vec(),
+            verbosity: Verbosity::Normal,
+            regions: None,
+            diagonals: false,
+            windows: false,
+            relations: vec!(),
+            non_consecutive: false,
+            size: None,
+            layout: None,
+            symmetry: Symmetry::Central,
+            seed: None,
+            threads: None
+        }
+    }
+
+    fn has_variants(&self) -> bool {
+        self.regions.is_some() || self.diagonals || self.windows || !self.relations.is_empty() || self.non_consecutive
+    }
+}
+
+// Reads the command line, without the program name.  Without a command it solves: the path
+// given first, or stdin.
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let (command, rest) = match args.first().and_then(|name| Command::from_name(name)) {
+        Some(command) => (command, &args[1..]),
+        None => (Command::Solve, args)
+    };
+    let mut options = Options::new(command);
+
+    let mut rest = rest.iter();
+    while let Some(arg) = rest.next() {
+        let mut value = || rest.next().map(String::as_str).ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--input" => options.input = match value()? {
+                "auto" => InputFormat::Auto,
+                "pencilmarks" => InputFormat::Pencilmarks,
+                "candidates" => InputFormat::Candidates,
+                "hodoku" => InputFormat::Hodoku,
+                other => return Err(format!("unknown input format `{}`", other))
+            },
+            "--format" => options.format = match value()? {
+                "grid" => OutputFormat::Grid,
+                "line" => OutputFormat::Line,
+                "ss" => OutputFormat::Ss,
+                "pencilmarks" => OutputFormat::Pencilmarks,
+                "json" if cfg!(feature = "serde") => OutputFormat::Json,
+                "json" => return Err("json output needs the serde feature".into()),
+                other => return Err(format!("unknown output format `{}`", other))
+            },
+            "--strategies" => {
+                options.strategies = value()?.split(',')
+                    .map(|name| Strategy::from_name(name.trim()).ok_or(format!("unknown strategy `{}`", name)))
+                    .collect::<Result<Vec<Strategy>, String>>()?;
+            },
+            "--output" => options.output = Some(value()?.to_string()),
+            "--quiet" => options.verbosity = Verbosity::Quiet,
+            "--verbose" => options.verbosity = Verbosity::Verbose,
+            "--regions" => options.regions = Some(value()?.to_string()),
+            "--diagonals" => options.diagonals = true,
+            "--windows" => options.windows = true,
+            "--anti-knight" => options.relations.push(Relation::AntiKnight),
+            "--anti-king" => options.relations.push(Relation::AntiKing),
+            "--non-consecutive" => options.non_consecutive = true,
+            "--size" => {
+                let size = value()?;
+                options.size = size.split_once('x')
+                    .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+                    .map(Some)
+                    .ok_or(format!("expected a box size such as 3x3, not `{}`", size))?;
+            },
+            "--layout" => options.layout = Some(value()?.to_string()),
+            "--symmetry" => {
+                let name = value()?;
+                options.symmetry = Symmetry::from_name(name).ok_or(format!("unknown symmetry `{}`", name))?;
+            },
+            "--seed" => {
+                let seed = value()?;
+                options.seed = Some(seed.parse().map_err(|_| format!("expected a number for the seed, not `{}`", seed))?);
+            },
+            "--threads" => {
+                let threads = value()?;
+                options.threads = Some(threads.parse().ok().filter(|&n| n > 0)
+                    .ok_or(format!("expected a positive number of threads, not `{}`", threads))?);
+            },
+            path if path == "-" || !path.starts_with("--") => match (&options.path, &options.output) {
+                (None, _) => options.path = Some(path.to_string()),
+                (Some(_), None) if command == Command::Batch => options.output = Some(path.to_string()),
+                _ => return Err(format!("unexpected argument `{}`", path))
+            },
+            other => return Err(format!("unexpected argument `{}`", other))
+        }
+    }
+
+    if command == Command::Generate && options.path.is_some() {
+        return Err("generate does not read a puzzle".into())
+    }
+    if options.input != InputFormat::Auto && options.has_variants() {
+        return Err("variant options only apply to --input auto".into())
+    }
+    if command == Command::Batch && (options.input != InputFormat::Auto || options.format != OutputFormat::Grid || options.has_variants()) {
+        return Err("batch reads and writes its own formats, and takes no variant options".into())
+    }
+    if command != Command::Batch && options.threads.is_some() {
+        return Err("only batch takes --threads".into())
+    }
+    Ok(options)
+}
+
+// The exit code for a failure, so scripts can tell bad input from a puzzle without a single solution.
+fn exit_code(err: &SudokuError) -> i32 {
+    match err {
+        SudokuError::Io(_) => EXIT_ERROR,
+        SudokuError::Unsolvable | SudokuError::Contradiction(_) => EXIT_UNSOLVABLE,
+        SudokuError::MultipleSolutions => EXIT_MULTIPLE,
+        SudokuError::Parse {..} | SudokuError::IndexOutOfRange(_) | SudokuError::DigitOutOfRange(_) |
+        SudokuError::DuplicateEntry(_) | SudokuError::InvalidPermutation | SudokuError::InvalidGeometry(_) |
+        SudokuError::Invalid(_) => EXIT_INVALID
+    }
+}
+
+fn status_code(status: Status) -> i32 {
+    match status {
+        Status::Solved => EXIT_SOLVED,
+        Status::Invalid => EXIT_INVALID,
+        Status::Unsolvable => EXIT_UNSOLVABLE,
+        Status::Multiple => EXIT_MULTIPLE,
+        Status::Error => EXIT_ERROR
+    }
+}
 
 fn main() {
-    println!("Sudoku Solver");
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if let Some("--help" | "-h" | "help") = args.first().map(String::as_str) {
+        println!("{}", USAGE);
+        return
+    }
+
+    let options = parse_args(&args).unwrap_or_else(|message| {
+        eprintln!("{}\n\n{}", message, USAGE);
+        process::exit(EXIT_USAGE);
+    });
+    if options.verbosity == Verbosity::Verbose {
+        eprintln!("Sudoku Solver");
+    }
+
+    let code = open_output(&options).and_then(|mut out| {
+        let code = run(&options, &mut out)?;
+        out.flush()?;
+        Ok(code)
+    }).unwrap_or_else(|err| {
+        if options.verbosity > Verbosity::Quiet {
+            eprintln!("{}", err);
+            if let SudokuError::Invalid(violations) = &err {
+                for violation in violations {
+                    eprintln!("  {:?}", violation);
+                }
+            }
+        }
+        exit_code(&err)
+    });
+    process::exit(code);
+}
+
+// Where the requested output goes: stdout, or the --output file.
+fn open_output(options: &Options) -> Result<Box<dyn Write>, SudokuError> {
+    Ok(match &options.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout().lock())
+    })
+}
+
+// Carries out the command, writing the requested output to `out` and returning the exit code.
+fn run(options: &Options, out: &mut dyn Write) -> Result<i32, SudokuError> {
+    let verbose = options.verbosity == Verbosity::Verbose;
+    let quiet = options.verbosity == Verbosity::Quiet;
+
+    if options.command == Command::Batch {
+        return batch(options, out)
+    }
+
+    if options.command == Command::Generate {
+        let seed = options.seed.unwrap_or_else(|| {
+            SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64)
+        });
+        let puzzle = generate(Arc::new(generate_geometry(options)?), options.symmetry, seed)?;
+        if verbose {
+            eprintln!("Seed {}", seed);
+        }
+        write!(out, "{}", write_output(&Snapshot::new(puzzle), options.format)?)?;
+        return Ok(EXIT_SOLVED)
+    }
+
+    let snapshot = read_input(options)?;
+    if verbose {
+        eprint!("{}", write_puzzle(&snapshot.givens));
+    }
+
+    match options.command {
+        Command::Solve | Command::Validate | Command::Rate if options.format == OutputFormat::Json => {
+            let report = SolveReport::from_snapshot(&snapshot, &options.strategies);
+            write!(out, "{}", to_json(&report))?;
+            return Ok(status_code(report.status))
+        },
+        Command::Solve => {
+            let mut solver = solver(snapshot, options)?;
+            let solved = solver.solve();
+            if verbose {
+                eprint!("{}", write_progress(&solver));
+            }
+            solved?;
+            // Logical steps are sound, so the solution found from them is the puzzle's own
+            let solution = if solver.is_solved() { solver.data().clone() } else { solve_unique(solver.data())? };
+            write!(out, "{}", write_output(&Snapshot::new(solution), options.format)?)?;
+        },
+        Command::Validate => {
+            let violations = validate(&snapshot.data);
+            if !violations.is_empty() {
+                return Err(SudokuError::Invalid(violations))
+            }
+            match count_solutions(&snapshot.data, 2) {
+                0 => return Err(SudokuError::Unsolvable),
+                1 => (),
+                _ => return Err(SudokuError::MultipleSolutions)
+            }
+            if !quiet {
+                writeln!(out, "valid")?;
+            }
+        },
+        Command::Rate => {
+            solve_unique(&snapshot.data)?;
+            let mut solver = solver(snapshot, options)?;
+            solver.solve()?;
+            if verbose {
+                eprint!("{}", write_progress(&solver));
+            }
+            writeln!(out, "{}", Rating::from_solver(&solver).name())?;
+        },
+        Command::Hint => {
+            let mut solver = solver(snapshot, options)?;
+            if solver.is_solved() {
+                if !quiet {
+                    writeln!(out, "already solved")?;
+                }
+                return Ok(EXIT_SOLVED)
+            }
+            match solver.next_step()? {
+                Some(step) if options.format == OutputFormat::Json => write!(out, "{}", to_json(step))?,
+                Some(step) => writeln!(out, "{}", describe(step))?,
+                None => {
+                    if options.format == OutputFormat::Json {
+                        writeln!(out, "null")?;
+                    }
+                    if !quiet {
+                        eprintln!("No logical step found with these strategies");
+                    }
+                    return Ok(EXIT_STUCK)
+                }
+            }
+        },
+        Command::Convert => write!(out, "{}", write_output(&snapshot, options.format)?)?,
+        Command::Generate | Command::Batch => unreachable!()
+    }
+    Ok(EXIT_SOLVED)
+}
+
+fn solver(snapshot: Snapshot, options: &Options) -> Result<Solver, SudokuError> {
+    let mut solver = snapshot.into_solver()?;
+    solver.set_strategies(&options.strategies);
+    Ok(solver)
+}
+
+// A step as one line, such as `hidden-single: 5 at 12`.
+fn describe(step: &Step) -> String {
+    let change = match step.placement {
+        Some((idx, value)) => format!("{} at {}", value, idx),
+        None => {
+            let eliminations: Vec<String> = step.eliminations.iter().map(|(idx, value)| format!("{} from {}", value, idx)).collect();
+            format!("remove {}", eliminations.join(", "))
+        }
+    };
+    format!("{}: {}", step.strategy.name(), change)
+}
+
+fn write_output(snapshot: &Snapshot, format: OutputFormat) -> Result<String, SudokuError> {
+    let data = &snapshot.data;
+    match format {
+        OutputFormat::Grid => Ok(write_puzzle(data)),
+        OutputFormat::Line => Ok(format!("{}\n", data)),
+        OutputFormat::Ss if data.geometry().grids().len() > 1 => {
+            Err(SudokuError::InvalidGeometry("the .ss format holds a single grid".into()))
+        },
+        OutputFormat::Ss => Ok(write_ss(data)),
+        OutputFormat::Pencilmarks => Ok(write_grid(data, &snapshot.possibles)),
+        OutputFormat::Json => Ok(to_json(data))
+    }
+}
 
-    let filepath = env::args().nth(1).expect("No filepath given");
+#[cfg(feature = "serde")]
+fn to_json<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string(value).expect("grids, steps and reports always serialize") + "\n"
+}
 
-    //let data_map = read_puzzle_file(&filepath).expect("Could not read provided file");
-    let initial_data = parse_from_file(&filepath).expect("Error reading puzzle");
+// `--format json` is refused when parsing the arguments without the feature
+#[cfg(not(feature = "serde"))]
+fn to_json<T>(_: &T) -> String {
+    unreachable!("json output needs the serde feature")
+}
 
-    let mut solver = Solver::new(initial_data);
-    // solver.solution.print();
-    print_puzzle(&solver.initial_data());
-    solver.solve();
-    print_puzzle(&solver.data());
+// The text of the input file, or of stdin.
+fn read_content(options: &Options) -> Result<String, SudokuError> {
+    match options.path.as_deref() {
+        None | Some("-") => {
+            let mut content = String::new();
+            io::stdin().read_to_string(&mut content)?;
+            Ok(content)
+        },
+        Some(path) => Ok(fs::read_to_string(path)?)
+    }
+}
 
+fn read_input(options: &Options) -> Result<Snapshot, SudokuError> {
+    let content = read_content(options)?;
+
+    match options.input {
+        InputFormat::Auto => parse_puzzle(&content, options).map(Snapshot::new),
+        InputFormat::Pencilmarks | InputFormat::Candidates => {
+            let (placed, possibles) = match options.input {
+                InputFormat::Pencilmarks => parse_grid(&content)?,
+                _ => parse_candidates(&content)?
+            };
+            // Only the digits marked as placed stand as givens; cells down to one candidate
+            // stay open with their pencilmarks
+            Ok(Snapshot {possibles, ..Snapshot::new(placed)})
+        },
+        InputFormat::Hodoku => read_hodoku(content.lines().find(|line| !line.trim().is_empty()).unwrap_or(""))
+    }
+}
 
+// Reads the puzzle, onto a jigsaw layout and with any extra units or global rules given by the options.
+fn parse_puzzle(content: &str, options: &Options) -> Result<Data, SudokuError> {
+    if !options.has_variants() {
+        return parse_from_str(content);
+    }
+
+    let geometry = match &options.regions {
+        Some(path) => read_regions(path)?,
+        None => {
+            // Only the board shape, since any cages are added again along with the grid
+            let parsed = parse_from_str(content)?;
+            let shape = parsed.geometry();
+            Geometry::overlapping(shape.box_width(), shape.box_height(), shape.grids().to_vec())?
+        }
+    };
+    parse_with_geometry(content, Arc::new(with_variants(geometry, options)))
+}
+
+fn with_variants(mut geometry: Geometry, options: &Options) -> Geometry {
+    if options.diagonals {
+        geometry = geometry.with_diagonals();
+    }
+    if options.windows {
+        geometry = geometry.with_windows();
+    }
+    for relation in options.relations.iter() {
+        geometry = geometry.with_relation(*relation);
+    }
+    if options.non_consecutive {
+        geometry = geometry.with_non_consecutive();
+    }
+    geometry
+}
+
+// The board to generate on: a jigsaw, a named layout or a box size, 3×3 by default.
+fn generate_geometry(options: &Options) -> Result<Geometry, SudokuError> {
+    let geometry = match (&options.regions, &options.layout, options.size) {
+        (Some(path), _, _) => read_regions(path)?,
+        (None, Some(name), _) => Geometry::layout(name).ok_or(SudokuError::InvalidGeometry(format!("unknown layout `{}`", name)))?,
+        (None, None, Some((width, height))) => Geometry::new(width, height)?,
+        (None, None, None) => Geometry::new(3, 3)?
+    };
+    Ok(with_variants(geometry, options))
+}
+
+// Solves a collection, writing a results line per puzzle.  The summary is a message, so it goes
+// to stderr.
+fn batch(options: &Options, out: &mut dyn Write) -> Result<i32, SudokuError> {
+    let entries = parse_collection(&read_content(options)?);
+    let threads = options.threads.unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+    let start = Instant::now();
+    let results = run_batch(&entries, threads, &options.strategies);
+    let throughput = Throughput::from_results(&results, start.elapsed());
+    write_results(&results, out)?;
+
+    if options.verbosity > Verbosity::Quiet {
+        let solved = results.iter().filter(|r| r.outcome.is_ok()).count();
+        eprintln!("Solved {} of {} puzzles on {} threads", solved, results.len(), threads);
+        eprintln!(
+            "{:.1} puzzles/second, median {:?}, p99 {:?}",
+            throughput.per_second, throughput.median, throughput.p99
+        );
+    }
+    Ok(EXIT_SOLVED)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options = parse_args(&args("puzzles/puzzle_1.txt --diagonals")).unwrap();
+        assert_eq!(options.command, Command::Solve);
+        assert_eq!(options.path.as_deref(), Some("puzzles/puzzle_1.txt"));
+        assert!(options.diagonals);
+
+        let options = parse_args(&args("hint - --input hodoku --strategies naked-single,hidden-single --quiet")).unwrap();
+        assert_eq!(options.command, Command::Hint);
+        assert_eq!(options.path.as_deref(), Some("-"));
+        assert_eq!(options.input, InputFormat::Hodoku);
+        assert_eq!(options.strategies, vec!(Strategy::NakedSingle, Strategy::HiddenSingle));
+        assert_eq!(options.verbosity, Verbosity::Quiet);
+
+        let options = parse_args(&args("generate --size 3x2 --symmetry none --seed 7 --format line")).unwrap();
+        assert_eq!((options.size, options.symmetry, options.seed), (Some((3, 2)), Symmetry::None, Some(7)));
+        assert_eq!(options.format, OutputFormat::Line);
+
+        assert_eq!(parse_args(&args("convert --format pencilmarks")).unwrap().format, OutputFormat::Pencilmarks);
+        assert_eq!(parse_args(&args("solve --format json")).is_ok(), cfg!(feature = "serde"));
+
+        let options = parse_args(&[]).unwrap();
+        assert_eq!((options.command, options.path), (Command::Solve, None));
+        assert_eq!(parse_args(&args("--format line")).unwrap().command, Command::Solve);
+
+        assert!(parse_args(&args("rate --format xml")).is_err());
+        assert!(parse_args(&args("rate --strategies guessing")).is_err());
+        assert!(parse_args(&args("solve a.txt b.txt")).is_err());
+        assert!(parse_args(&args("solve --seed")).is_err());
+        assert!(parse_args(&args("convert --input hodoku --diagonals")).is_err());
+
+        let options = parse_args(&args("--batch in.txt out.tsv --threads 2 --strategies naked-single")).unwrap();
+        assert_eq!(options.command, Command::Batch);
+        assert_eq!((options.path.as_deref(), options.output.as_deref()), (Some("in.txt"), Some("out.tsv")));
+        assert_eq!((options.threads, options.strategies), (Some(2), vec!(Strategy::NakedSingle)));
+        assert_eq!(parse_args(&args("batch - --output out.tsv")).unwrap().output.as_deref(), Some("out.tsv"));
+        assert!(parse_args(&args("batch in.txt --threads 0")).is_err());
+        assert!(parse_args(&args("batch in.txt --format line")).is_err());
+        assert!(parse_args(&args("solve in.txt --threads 2")).is_err());
+    }
+
+    #[test]
+    fn test_hint_before_contradiction() {
+        // The first naked single is sound, though the singles after it run a cell out of digits
+        let path = env::temp_dir().join(format!("sudoku-hint-{}.txt", process::id()));
+        fs::write(&path, ".32...1.3......3\n").unwrap();
+        let options = parse_args(&args(&format!("hint {}", path.display()))).unwrap();
+        let mut out = vec!();
+        let code = run(&options, &mut out);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(code.unwrap(), EXIT_SOLVED);
+        assert_eq!(String::from_utf8(out).unwrap(), "naked-single: 4 at 3\n");
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(exit_code(&SudokuError::MultipleSolutions), EXIT_MULTIPLE);
+        assert_eq!(exit_code(&SudokuError::Contradiction(3)), EXIT_UNSOLVABLE);
+        assert_eq!(exit_code(&SudokuError::Invalid(vec!())), EXIT_INVALID);
+        assert_eq!(exit_code(&SudokuError::Parse {line: 1, column: 1, message: String::new()}), EXIT_INVALID);
+        assert_eq!(exit_code(&SudokuError::Io(io::Error::other("closed"))), EXIT_ERROR);
+    }
 }
@@ -0,0 +1,210 @@
+use std::sync::Arc;
+
+use crate::{Data, Geometry, Index, SudokuError};
+use crate::backtrack::{first_solution, has_unique_solution, solve_unique};
+use crate::random::Rng;
+
+/// A symmetry of the board, used to keep clue layouts symmetric while removing givens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symmetry {
+    None,
+    /// 180 degree rotation about the centre cell
+    Central,
+    /// 90 degree rotation about the centre cell
+    Quarter,
+    /// Mirror across the middle row
+    Horizontal,
+    /// Mirror across the middle column
+    Vertical,
+    /// Mirror across the top left to bottom right diagonal
+    Diagonal,
+    /// Mirror across the top right to bottom left diagonal
+    AntiDiagonal
+}
+
+impl Symmetry {
+    const ALL: [Symmetry; 7] = [
+        Symmetry::None, Symmetry::Central, Symmetry::Quarter, Symmetry::Horizontal,
+        Symmetry::Vertical, Symmetry::Diagonal, Symmetry::AntiDiagonal
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Symmetry::None => "none",
+            Symmetry::Central => "central",
+            Symmetry::Quarter => "quarter",
+            Symmetry::Horizontal => "horizontal",
+            Symmetry::Vertical => "vertical",
+            Symmetry::Diagonal => "diagonal",
+            Symmetry::AntiDiagonal => "anti-diagonal"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Symmetry> {
+        Symmetry::ALL.into_iter().find(|symmetry| symmetry.name() == name)
+    }
+
+    fn image(&self, (row, column): (usize, usize), last: usize) -> (usize, usize) {
+        match self {
+            Symmetry::None => (row, column),
+            Symmetry::Central => (last - row, last - column),
+            Symmetry::Quarter => (column, last - row),
+            Symmetry::Horizontal => (last - row, column),
+            Symmetry::Vertical => (row, last - column),
+            Symmetry::Diagonal => (column, row),
+            Symmetry::AntiDiagonal => (last - column, last - row)
+        }
+    }
+
+    /// All the indices the symmetry maps `idx` onto on a board of side `size`, including
+    /// `idx` itself.
+    pub fn orbit(&self, idx: Index, size: usize) -> Vec<Index> {
+        let mut orbit = vec!(idx);
+        let mut next = self.image((idx / size, idx % size), size - 1);
+        while size * next.0 + next.1 != idx {
+            orbit.push(size * next.0 + next.1);
+            next = self.image(next, size - 1);
+        }
+        orbit
+    }
+
+    /// The orbit of `idx` on the canvas of a geometry, so overlapping grids are mapped as a
+    /// whole.  Images falling in the gaps between grids are left out.
+    pub fn orbit_in(&self, idx: Index, geometry: &Geometry) -> Vec<Index> {
+        let position = geometry.position_from_index(idx);
+        let start = (position.row(), position.column());
+        let last = geometry.width().max(geometry.height()) - 1;
+
+        let mut orbit = vec!(idx);
+        let mut next = self.image(start, last);
+        while next != start {
+            orbit.extend(geometry.index_at(next.0, next.1));
+            next = self.image(next, last);
+        }
+        orbit
+    }
+}
+
+/// The givens which could be removed individually without losing a unique solution.
+///
+/// Fails for puzzles which do not have a unique solution to begin with.
+pub fn redundant_givens(data: &Data) -> Result<Vec<Index>, SudokuError> {
+    solve_unique(data)?;
+
+    let mut trial = data.clone();
+    let mut redundant = vec!();
+
+    for idx in (0..data.len()).filter(|&i| data[i].is_some()) {
+        trial[idx] = None;
+        if has_unique_solution(&trial) {
+            redundant.push(idx);
+        }
+        trial[idx] = data[idx];
+    }
+    Ok(redundant)
+}
+
+/// True when the puzzle has a unique solution and every given is needed to keep it that way.
+pub fn is_minimal(data: &Data) -> bool {
+    redundant_givens(data).is_ok_and(|redundant| redundant.is_empty())
+}
+
+/// Strips redundant givens from a puzzle until no further symmetric group of givens can be
+/// removed without losing uniqueness.
+///
+/// Givens are removed together with their images under `symmetry`, so a symmetric puzzle
+/// stays symmetric.  With `Symmetry::None` the result is minimal in the strict sense.
+/// Fails when the puzzle does not have a unique solution.
+pub fn minimize(data: &Data, symmetry: Symmetry) -> Result<Data, SudokuError> {
+    solve_unique(data)?;
+    Ok(remove_givens(data, symmetry, 0..data.len()))
+}
+
+// Tries removing each given in turn, in the order given, along with its images.
+fn remove_givens(data: &Data, symmetry: Symmetry, order: impl Iterator<Item = Index>) -> Data {
+    let mut puzzle = data.clone();
+
+    for idx in order {
+        if puzzle[idx].is_none() {
+            continue
+        }
+
+        let orbit = symmetry.orbit_in(idx, data.geometry());
+        let mut trial = puzzle.clone();
+        for &i in orbit.iter() {
+            trial[i] = None;
+        }
+
+        if has_unique_solution(&trial) {
+            puzzle = trial;
+        }
+    }
+    puzzle
+}
+
+/// Generates a puzzle with a unique solution on a geometry, along with any rules it carries,
+/// from a seed.  A solution is filled from a few random digits, then givens are removed in a
+/// random order, keeping to `symmetry`, until none more can go.
+///
+/// Fails when the rules of the geometry leave no solution at all.
+pub fn generate(geometry: Arc<Geometry>, symmetry: Symmetry, seed: u64) -> Result<Data, SudokuError> {
+    let mut rng = Rng::new(seed);
+    let mut data = Data::with_geometry(geometry.clone());
+    first_solution(&data).ok_or(SudokuError::Unsolvable)?;
+
+    // Enough random digits to set the search off somewhere different each time
+    for _ in 0..geometry.size() {
+        let idx = rng.below(data.len());
+        let value = rng.below(geometry.size()) as u8 + 1;
+        if data[idx].is_none() {
+            data[idx] = Some(value);
+            if first_solution(&data).is_none() {
+                data[idx] = None;
+            }
+        }
+    }
+
+    let solution = first_solution(&data).ok_or(SudokuError::Unsolvable)?;
+    let mut order: Vec<Index> = (0..solution.len()).collect();
+    rng.shuffle(&mut order);
+    Ok(remove_givens(&solution, symmetry, order.into_iter()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::parse_from_file;
+
+    #[test]
+    fn test_orbits() {
+        assert_eq!(Symmetry::None.orbit(10, 9), vec!(10));
+        assert_eq!(Symmetry::Central.orbit(0, 9), vec!(0, 80));
+        assert_eq!(Symmetry::Central.orbit(40, 9), vec!(40));
+        assert_eq!(Symmetry::Quarter.orbit(0, 9), vec!(0, 8, 80, 72));
+        assert_eq!(Symmetry::Diagonal.orbit(1, 9), vec!(1, 9));
+        assert_eq!(Symmetry::Quarter.orbit(0, 4), vec!(0, 3, 15, 12));
+    }
+
+    #[test]
+    fn test_generate() {
+        let puzzle = generate(Geometry::classic(), Symmetry::Central, 7).unwrap();
+        assert!(has_unique_solution(&puzzle));
+        assert_eq!(puzzle, generate(Geometry::classic(), Symmetry::Central, 7).unwrap());
+        assert_ne!(puzzle, generate(Geometry::classic(), Symmetry::Central, 8).unwrap());
+        for idx in (0..81).filter(|&idx| puzzle[idx].is_some()) {
+            assert!(puzzle[80 - idx].is_some());
+        }
+        assert_eq!(Symmetry::from_name("anti-diagonal"), Some(Symmetry::AntiDiagonal));
+    }
+
+    #[test]
+    fn test_minimize() {
+        let data = parse_from_file("puzzles/puzzle_2.txt").unwrap();
+        let minimal = minimize(&data, Symmetry::None).unwrap();
+
+        assert!(is_minimal(&minimal));
+        assert!(redundant_givens(&minimal).unwrap().is_empty());
+        assert!(matches!(minimize(&Data::empty(), Symmetry::None), Err(SudokuError::MultipleSolutions)));
+        assert!(minimal.iter().zip(data.iter()).all(|(m, d)| m.is_none() || m == d));
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::{Data, Geometry, Index, Value, MAX_SIZE, Possibles, SudokuError};
+
+/// Marks a placed digit in both formats, telling it apart from an open cell with a single
+/// candidate left.
+pub const PLACED: char = '*';
+
+// A cell as written in either format.
+enum Mark {
+    Placed(Value),
+    Candidates(Vec<Value>)
+}
+
+// The candidates of an open cell, sorted.
+fn cell_candidates(possibles: &Possibles, idx: Index) -> Vec<Value> {
+    let mut candidates: Vec<Value> = possibles.candidates(&idx).into_iter().flatten().copied().collect();
+    candidates.sort();
+    candidates
+}
+
+// Only the cells marked as placed are filled in; the rest, however few candidates they have
+// left, make up the possibles.
+fn from_marks(geometry: Arc<Geometry>, marks: Vec<Mark>) -> (Data, Possibles) {
+    let mut data = Data::with_geometry(geometry);
+    let mut by_cells: HashMap<Index, HashSet<Value>> = HashMap::new();
+
+    for (idx, mark) in marks.into_iter().enumerate() {
+        match mark {
+            Mark::Placed(value) => data[idx] = Some(value),
+            Mark::Candidates(candidates) => {
+                by_cells.insert(idx, HashSet::from_iter(candidates));
+            }
+        }
+    }
+    (data, Possibles::from_cells(by_cells))
+}
+
+/// Writes the candidate format: a character per digit per cell (729 for a 9×9 grid), with
+/// digit `d` in position `d` when it is a candidate and `.` otherwise.  A placed cell shows its
+/// value with `PLACED` in the other positions.
+pub fn write_candidates(data: &Data, possibles: &Possibles) -> String {
+    let geometry = data.geometry();
+    let mut out = String::with_capacity(data.len() * geometry.size());
+    for idx in 0..data.len() {
+        let (candidates, absent) = match data[idx] {
+            Some(value) => (vec!(value), PLACED),
+            None => (cell_candidates(possibles, idx), '.')
+        };
+        for value in geometry.values() {
+            out.push(if candidates.contains(&value) { geometry.digit_char(value) } else { absent });
+        }
+    }
+    out
+}
+
+/// Reads the candidate format, accepting `.` or `0` for absent candidates and ignoring
+/// whitespace.  The size comes from the length, 729 being 9×9.
+///
+/// Only cells marked with `PLACED` read back as placed, and a cell with a single candidate
+/// stays open, so any state saved with `write_candidates` is restored exactly.
+pub fn parse_candidates(s: &str) -> Result<(Data, Possibles), SudokuError> {
+    let mut chars = vec!();
+    let (mut line, mut column) = (1, 0);
+
+    for c in s.chars() {
+        column += 1;
+        if c == '\n' {
+            line += 1;
+            column = 0;
+            continue
+        }
+        if !c.is_whitespace() {
+            chars.push((c, line, column));
+        }
+    }
+
+    let size = (2..=MAX_SIZE).find(|n| n * n * n == chars.len()).ok_or_else(|| SudokuError::Parse {
+        line, column, message: format!("expected a cube number of candidates such as 729, found {}", chars.len())
+    })?;
+    let geometry = Geometry::for_size(size)?;
+
+    let mut marks = vec!();
+    for cell in chars.chunks(size) {
+        let placed = cell.iter().any(|&(c, _, _)| c == PLACED);
+        let mut candidates = vec!();
+        for (expected, &(c, line, column)) in (1..).zip(cell) {
+            match c {
+                '.' | '0' if !placed => {},
+                PLACED => {},
+                _ if geometry.parse_digit(c) == Some(expected) => candidates.push(expected),
+                _ => return Err(SudokuError::Parse {line, column, message: format!("unexpected character `{}`", c)})
+            }
+        }
+        marks.push(match (placed, candidates.as_slice()) {
+            (true, &[value]) => Mark::Placed(value),
+            (false, _) => Mark::Candidates(candidates),
+            (true, _) => {
+                let (_, line, column) = cell[0];
+                return Err(SudokuError::Parse {line, column, message: "expected one digit in a placed cell".into()})
+            }
+        });
+    }
+    Ok(from_marks(geometry, marks))
+}
+
+/// Writes the human readable pencilmark grid, listing each cell's candidates in a boxed layout.
+/// Placed digits are marked with `PLACED`, as in `*5`.  Overlapping grids are drawn on their
+/// shared canvas, with the gaps between them left blank.
+pub fn write_grid(data: &Data, possibles: &Possibles) -> String {
+    let geometry = data.geometry();
+    let (width, height) = (geometry.width(), geometry.height());
+    // Jigsaw regions don't line up with the box borders, so only the outside border is drawn
+    let (box_width, box_height) = match geometry.is_jigsaw() {
+        true => (width, height),
+        false => (geometry.box_width(), geometry.box_height())
+    };
+    let cells: Vec<String> = (0..data.len()).map(|idx| match data[idx] {
+        Some(value) => format!("{}{}", PLACED, geometry.digit_char(value)),
+        None => cell_candidates(possibles, idx).iter().map(|v| geometry.digit_char(*v)).collect()
+    }).collect();
+
+    let widths: Vec<usize> = (0..width).map(|column| {
+        (0..height).filter_map(|row| geometry.index_at(row, column)).map(|idx| cells[idx].len()).max().unwrap_or(0).max(1)
+    }).collect();
+    let stacks: Vec<&[usize]> = widths.chunks(box_width).collect();
+
+    // Whether the box at a canvas row and stack is part of a grid
+    let present = |row: usize, stack: usize| row < height && geometry.index_at(row, stack * box_width).is_some();
+    // Joins the pieces drawn for each stack, closing a box with `edge` when the stack after it
+    // is empty or the last
+    let join = |pieces: Vec<(bool, String)>, edge: char| -> String {
+        let mut line = String::new();
+        for (k, (shown, piece)) in pieces.iter().enumerate() {
+            let opened = *shown || (k > 0 && pieces[k - 1].0);
+            line.push(if opened { edge } else { ' ' });
+            line.push_str(piece);
+        }
+        if pieces.last().is_some_and(|(shown, _)| *shown) {
+            line.push(edge);
+        }
+        format!("{}\n", line.trim_end())
+    };
+    let border = |row: usize| join((0..stacks.len()).map(|stack| {
+        let shown = present(row, stack) || (row > 0 && present(row - 1, stack));
+        let length = stacks[stack].iter().sum::<usize>() + box_width + 1;
+        (shown, if shown { "-".repeat(length) } else { " ".repeat(length) })
+    }).collect(), '+');
+
+    let mut out = String::new();
+    for row in 0..height {
+        if row % box_height == 0 {
+            out.push_str(&border(row));
+        }
+        out.push_str(&join((0..stacks.len()).map(|stack| {
+            let shown = present(row, stack);
+            let mut piece = String::from(" ");
+            for (k, width) in stacks[stack].iter().enumerate() {
+                let cell = geometry.index_at(row, stack * box_width + k).map_or("", |idx| cells[idx].as_str());
+                piece.push_str(&format!("{:<width$} ", cell, width = width));
+            }
+            (shown, piece)
+        }).collect(), '|'));
+    }
+    out.push_str(&border(height));
+    out
+}
+
+/// Reads a pencilmark grid like the one from `write_grid`: a group of candidate digits per
+/// cell (81 for a 9×9 grid), in order, separated by whitespace or `|`.  A digit marked with
+/// `PLACED` is placed; a lone unmarked digit is a cell's last candidate.  Lines made up only
+/// of border characters are skipped.
+pub fn parse_grid(s: &str) -> Result<(Data, Possibles), SudokuError> {
+    let mut tokens = vec!();
+
+    for (n, line) in s.lines().enumerate() {
+        if line.chars().all(|c| c.is_whitespace() || "+-.:'|=*".contains(c)) {
+            continue
+        }
+
+        let mut column = 0;
+        for token in line.split(|c: char| c.is_whitespace() || c == '|') {
+            column += token.len() + 1;
+            if !token.is_empty() {
+                tokens.push((token, n + 1, column - token.len()));
+            }
+        }
+    }
+
+    let geometry = Geometry::from_cell_count(tokens.len()).ok_or_else(|| SudokuError::Parse {
+        line: s.lines().count(), column: 1, message: format!("expected a square number of cells such as 81, found {}", tokens.len())
+    })?;
+
+    let mut marks = vec!();
+    for (token, line, column) in tokens {
+        let (placed, digits) = match token.strip_prefix(PLACED) {
+            Some(digits) => (true, digits),
+            None => (false, token)
+        };
+        let mut candidates: Vec<Value> = vec!();
+        for c in digits.chars() {
+            match geometry.parse_digit(c) {
+                Some(value) if !candidates.contains(&value) => candidates.push(value),
+                _ => return Err(SudokuError::Parse {line, column, message: format!("unexpected character `{}`", c)})
+            }
+        }
+        marks.push(match (placed, candidates.as_slice()) {
+            (true, &[value]) => Mark::Placed(value),
+            (false, _) => Mark::Candidates(candidates),
+            (true, _) => return Err(SudokuError::Parse {line, column, message: "expected one digit in a placed cell".into()})
+        });
+    }
+    Ok(from_marks(geometry, marks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Solver;
+
+    #[test]
+    fn test_round_trip() {
+        let data: Data = "1....7.9..3..2...8..96..5....53..9...1..8...26....4...3......1..4......7..7...3..".parse().unwrap();
+        let mut solver = Solver::new(data).unwrap();
+        solver.solve().unwrap();
+        assert!(!solver.is_solved());
+
+        let candidates = write_candidates(solver.data(), solver.possibles());
+        assert_eq!(candidates.len(), 729);
+        assert_eq!(&candidates[..9], "1********");
+        assert_eq!(parse_candidates(&candidates).unwrap(), (solver.data().clone(), solver.possibles().clone()));
+
+        let grid = write_grid(solver.data(), solver.possibles());
+        assert_eq!(parse_grid(&grid).unwrap(), (solver.data().clone(), solver.possibles().clone()));
+    }
+
+    #[test]
+    fn test_single_candidates() {
+        // A cell down to its last candidate mid-solve stays open through both formats
+        let data: Data = "1....7.9..3..2...8..96..5....53..9...1..8...26....4...3......1..4......7..7...3..".parse().unwrap();
+        let mut possibles = Possibles::from_data(&data);
+        for value in [2, 5, 6] {
+            possibles.update(&1, &value);
+        }
+        assert_eq!(possibles.candidates(&1).unwrap().len(), 1);
+
+        let candidates = write_candidates(&data, &possibles);
+        assert_eq!(&candidates[9..18], ".......8.");
+        assert_eq!(parse_candidates(&candidates).unwrap(), (data.clone(), possibles.clone()));
+        assert_eq!(parse_grid(&write_grid(&data, &possibles)).unwrap(), (data, possibles));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse_candidates("123").is_err());
+        assert!(parse_candidates(&"21.......".repeat(81)).is_err());
+        assert!(parse_candidates(&"12*******".repeat(81)).is_err());
+        assert!(parse_grid("1 2 3").is_err());
+        assert!(parse_grid(&"*12 ".repeat(81)).is_err());
+    }
+
+    #[test]
+    fn test_small_grid() {
+        let data: Data = "12..............".parse().unwrap();
+        let possibles = Possibles::from_data(&data);
+
+        let candidates = write_candidates(&data, &possibles);
+        assert_eq!(candidates.len(), 64);
+        assert_eq!(parse_candidates(&candidates).unwrap(), (data.clone(), possibles.clone()));
+
+        let grid = write_grid(&data, &possibles);
+        assert_eq!(grid.lines().count(), 7);
+        assert!(grid.contains("| *1  *2  | 34   34   |"));
+        assert_eq!(parse_grid(&grid).unwrap(), (data, possibles));
+    }
+}
@@ -1,30 +1,35 @@
-use std::collections::{HashMap, HashSet};
-use itertools::sorted;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use itertools::{interleave, sorted};
 
-use crate::{Data, Index, Value, Indices};
+use crate::{Data, Geometry, Index, Value, Unit};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Possibles {
     by_cells: HashMap<Index, HashSet<Value>>,
     by_values: HashMap<Value, HashSet<Index>>
 }
 
+// `by_values` is the inverse of `by_cells`, but may hold empty sets for values that ran out.
+impl PartialEq for Possibles {
+    fn eq(&self, other: &Possibles) -> bool {
+        self.by_cells == other.by_cells
+    }
+}
+
+impl Eq for Possibles {}
+
 impl Possibles {
-    pub fn from_data(data: Data) -> Possibles {
+    pub fn from_data(data: &Data) -> Possibles {
         let mut by_cells: HashMap<usize, HashSet<u8>> = HashMap::new();
-        let mut by_values: HashMap<u8, HashSet<usize>> = HashMap::new();
-
-        for i in 1..10 {
-            by_values.insert(i, HashSet::new());
-        }
 
         for (idx, val) in data.iter().enumerate() {
             match val {
                 Some(_) => {},
                 None => {
-                    let mut possibles_set: HashSet<u8>  =HashSet::from_iter(1..10);
+                    let mut possibles_set: HashSet<u8>  =HashSet::from_iter(data.geometry().values());
 
-                    for oidx in Indices::around(idx) {
+                    for &oidx in data.geometry().around(idx) {
                         if let Some(val) = data[oidx] {
                             possibles_set.remove(&val);
                         }
@@ -35,11 +40,17 @@ impl Possibles {
             }
         }
 
+        Possibles::from_cells(by_cells)
+    }
+
+    /// Builds the possibles from the candidate values of each unsolved cell.
+    pub fn from_cells(by_cells: HashMap<Index, HashSet<Value>>) -> Possibles {
+        let mut by_values: HashMap<u8, HashSet<usize>> = HashMap::new();
+
         // Invert by_cells to get by_values
-        for (idx, vals) in by_cells.clone() {
+        for (idx, vals) in by_cells.iter() {
             for val in vals {
-                let hs = by_values.get_mut(&val).unwrap();
-                hs.insert(idx);
+                by_values.entry(*val).or_default().insert(*idx);
             }
         }
 
@@ -47,63 +58,60 @@ impl Possibles {
 
     }
 
-    pub fn clone(&self) -> Possibles {
-        Possibles { 
-            by_cells: self.by_cells.clone(), 
-            by_values: self.by_values.clone() 
-        }
+    /// The candidate values of an unsolved cell, or None if the cell is solved.
+    pub fn candidates(&self, index: &Index) -> Option<&HashSet<Value>> {
+        self.by_cells.get(index)
     }
-    
+
     pub fn len(&self) -> usize {
         self.by_cells.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_cells.is_empty()
+    }
+
     pub fn remove(&mut self, index: &Index) {
         self.by_cells.remove(index);
 
-        for val in 1..10 {
-            if let Some(idxs) = self.by_values.get_mut(&val) {
-                idxs.remove(index);
-            }
+        for idxs in self.by_values.values_mut() {
+            idxs.remove(index);
         }
     }
 
-    pub fn update(&mut self, index: &Index, value: &Value ) {
-        match self.by_cells.get_mut(index) {
-            Some(hs) => {
-                
-                match hs.remove(value) {
-                    true => (),
-                    false => ()
-                }},
-            None => ()
-        }
-        
-        match self.by_values.get_mut(value) {
-            Some(hs) => match hs.remove(index) {
-                true => (),
-                false => ()
-            },
-            None => ()
+    /// Removes a candidate value from a cell, returning whether it was still a candidate.
+    pub fn update(&mut self, index: &Index, value: &Value ) -> bool {
+        let removed = match self.by_cells.get_mut(index) {
+            Some(hs) => hs.remove(value),
+            None => false
+        };
+
+        if let Some(hs) = self.by_values.get_mut(value) {
+            hs.remove(index);
         }
-        
+        removed
     }
 
+
     pub fn find_singles(&self) -> Vec<(Index, Value)>  {
         self.by_cells.iter()
             .filter(|(_, v)| v.len() == 1 )
             .map(|(k, v)| (*k, *v.iter().next().unwrap())).collect()
     }
 
-    pub fn find_single_values(&self) -> Vec<(Index, Value)> {
+    /// The first cell (if any) left with no candidates, which means the grid has no solution.
+    pub fn find_empty(&self) -> Option<Index> {
+        sorted(self.by_cells.iter().filter(|(_, v)| v.is_empty()).map(|(k, _)| *k)).next()
+    }
+
+    pub fn find_single_values(&self, geometry: &Geometry) -> Vec<(Index, Value)> {
         let mut singles: Vec<(Index, Value)> = vec!();
-        for i in 0..9 {
-            for f in [Indices::row, Indices::column, Indices::subsquare] {
-                self.window(f(i)).iter().filter(|(_, v)| v.len() == 1)
-                    .map(|(k, v)| (v.iter().next().unwrap(), k))
-                    .for_each( |(&k, &v)|{
-                        singles.push((k, v));
-                    });
-            }
+        for (_, _, idxs) in geometry.units() {
+            self.window(idxs.to_vec()).iter().filter(|(_, v)| v.len() == 1)
+                .map(|(k, v)| (v.iter().next().unwrap(), k))
+                .for_each( |(&k, &v)|{
+                    singles.push((k, v));
+                });
         }
         singles
     }
@@ -120,66 +128,108 @@ impl Possibles {
         out_map
 
     }
-    fn find_overlays(&mut self, subsquare_idxs: &HashSet<Index>, other_idxs: HashSet<Index>) {
+    fn find_overlays(&mut self, subsquare_idxs: &HashSet<Index>, other_idxs: HashSet<Index>) -> Vec<(Index, Value)> {
+        let mut eliminated = vec!();
         let inner_idxs: HashSet<Index> = HashSet::from_iter(subsquare_idxs.intersection(&other_idxs).cloned());
         let outer_subsquare: HashSet<Index> = HashSet::from_iter(subsquare_idxs.difference(&other_idxs).cloned());
-        let outer_other: HashSet<Index> = HashSet::from_iter(other_idxs.difference(&subsquare_idxs).cloned());        
+        let outer_other: HashSet<Index> = HashSet::from_iter(other_idxs.difference(subsquare_idxs).cloned());        
 
         let vals_inner = self.window(Vec::from_iter(inner_idxs));
         let vals_outer_subsquare = self.window(Vec::from_iter(outer_subsquare.clone()));
         let vals_outer_other = self.window(Vec::from_iter(outer_other.clone()));
 
-        for (val, _) in vals_inner {
+        for val in sorted(vals_inner.into_keys()) {
             let outer_subsquare_has_val = vals_outer_subsquare.contains_key(&val);
             let outer_other_has_val = vals_outer_other.contains_key(&val);
 
             if !outer_other_has_val {
                 //println!("Removing overlay from subsquare: {} - {:?}", val, outer_subsquare);
-                for idx in outer_subsquare.clone() {
-                    self.update(&idx, &val);
+                for idx in sorted(outer_subsquare.clone()) {
+                    if self.update(&idx, &val) {
+                        eliminated.push((idx, val));
+                    }
                 }
             }
 
             if !outer_subsquare_has_val {
                 //println!("Removing overlay from other: {} - {:?}", val, outer_other);
-                for idx in outer_other.clone() {
-                    self.update(&idx, &val);
+                for idx in sorted(outer_other.clone()) {
+                    if self.update(&idx, &val) {
+                        eliminated.push((idx, val));
+                    }
                 }
             }
         }
-
+        eliminated
     }
-    pub fn remove_overlays(&mut self) {
-        let rows_idxs = (0..9).map(|i| Indices::row(i)).collect::<Vec<Vec<Index>>>();
-        let columns_idxs = (0..9).map(|i| Indices::column(i)).collect::<Vec<Vec<Index>>>();
-
-        for subsquare_id in 0..9 {
-            let subsquare_idxs: HashSet<Index> = HashSet::from_iter(Indices::subsquare(subsquare_id));
 
-            let super_row = subsquare_id / 3;
-            let super_column = subsquare_id % 3;
-
-            for row_id in 0..3 {
-                let row_ids: HashSet<Index> = HashSet::from_iter(rows_idxs[(3 * super_row) + row_id].clone());    
-                self.find_overlays(&subsquare_idxs, row_ids);
-                
-                let column_ids: HashSet<Index> = HashSet::from_iter(columns_idxs[(3 * super_column) + row_id].clone());
-                self.find_overlays(&subsquare_idxs, column_ids)
+    /// Removes candidates locked into a row or column within a subsquare or jigsaw region (or
+    /// the reverse), returning the `(index, value)` candidates it eliminated.
+    ///
+    /// Extra units such as diagonals are treated like subsquares, and also checked against the
+    /// subsquares and other extra units they cross.
+    pub fn remove_overlays(&mut self, geometry: &Geometry) -> Vec<(Index, Value)> {
+        let mut eliminated = vec!();
+        let lines = |unit: Unit| geometry.units().filter(move |(kind, _, _)| *kind == unit).map(|(_, _, idxs)| idxs);
+        let subsquares: Vec<&[Index]> = geometry.units()
+            .filter(|(kind, _, _)| !matches!(kind, Unit::Row | Unit::Column))
+            .map(|(_, _, idxs)| idxs)
+            .collect();
+
+        for (n, subsquare) in subsquares.iter().enumerate() {
+            let subsquare_idxs: HashSet<Index> = HashSet::from_iter(subsquare.iter().copied());
+
+            // The rows and columns crossing the subsquare, alternately, then the later
+            // subsquares or extra units crossing it
+            let crosses = |idxs: &&[Index]| idxs.iter().any(|idx| subsquare_idxs.contains(idx));
+            let crossing: Vec<&[Index]> = interleave(lines(Unit::Row).filter(crosses), lines(Unit::Column).filter(crosses))
+                .chain(subsquares[n + 1..].iter().copied().filter(crosses))
+                .collect();
+            for line in crossing {
+                eliminated.extend(self.find_overlays(&subsquare_idxs, HashSet::from_iter(line.iter().copied())));
             }
         }
+        eliminated
     }
 
-    pub fn print(&self) {
-        println!("By Indices");
+    /// The candidates of every unsolved cell, sorted by index and value.
+    pub fn to_cells(&self) -> BTreeMap<Index, Vec<Value>> {
+        self.by_cells.iter().map(|(idx, vals)| (*idx, sorted(vals.iter().copied()).collect())).collect()
+    }
 
+}
+
+/// The candidates by cell, then the cells by candidate, a line for each.
+impl fmt::Display for Possibles {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "By Indices")?;
         for idx in sorted(self.by_cells.keys()) {
-            println!("{} -> {:?}", idx, sorted(self.by_cells.get(idx).unwrap()).collect::<Vec<&Value>>());
+            writeln!(f, "{} -> {:?}", idx, sorted(self.by_cells.get(idx).unwrap()).collect::<Vec<&Value>>())?;
         }
 
-        println!("By Values");
+        writeln!(f, "By Values")?;
         for val in sorted(self.by_values.keys()) {
-            println!("{} -> {:?}", val, sorted(self.by_values.get(val).unwrap()).collect::<Vec<&Index>>());
+            writeln!(f, "{} -> {:?}", val, sorted(self.by_values.get(val).unwrap()).collect::<Vec<&Index>>())?;
         }
+        Ok(())
     }
+}
 
-}
\ No newline at end of file
+/// Serializes as a map from the index of each unsolved cell to its sorted candidates.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Possibles {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_cells().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Possibles {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Possibles, D::Error> {
+        let cells = BTreeMap::<Index, Vec<Value>>::deserialize(deserializer)?;
+        if let Some(value) = cells.values().flatten().find(|v| **v == 0 || **v as usize > crate::MAX_SIZE) {
+            return Err(serde::de::Error::custom(format!("candidate out of range [1, {}]: {}", crate::MAX_SIZE, value)));
+        }
+        Ok(Possibles::from_cells(cells.into_iter().map(|(idx, vals)| (idx, HashSet::from_iter(vals))).collect()))
+    }
+}
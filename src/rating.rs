@@ -0,0 +1,57 @@
+use crate::{Data, Solver, SudokuError};
+use crate::backtrack::solve_unique;
+use crate::solver::Strategy;
+
+/// How hard a puzzle is, by the hardest strategy the logical solver needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum Rating {
+    /// Naked singles only
+    Easy,
+    /// Needs hidden singles
+    Medium,
+    /// Needs locked candidates, or the rules of a variant such as dots, lines, clues or cages
+    Hard,
+    /// The logical strategies stall and search is needed
+    Fiendish
+}
+
+impl Rating {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rating::Easy => "easy",
+            Rating::Medium => "medium",
+            Rating::Hard => "hard",
+            Rating::Fiendish => "fiendish"
+        }
+    }
+
+    /// The rating earned by a solver which has finished its logical passes.
+    pub fn from_solver(solver: &Solver) -> Rating {
+        if !solver.is_solved() {
+            return Rating::Fiendish
+        }
+
+        match solver.steps().iter().map(|step| step.strategy).max() {
+            None | Some(Strategy::NakedSingle) => Rating::Easy,
+            Some(Strategy::HiddenSingle) => Rating::Medium,
+            Some(Strategy::LockedCandidates | Strategy::Constraints | Strategy::InniesOuties) => Rating::Hard
+        }
+    }
+}
+
+/// Rates a puzzle, failing when it is invalid or does not have a unique solution.
+pub fn rate(data: &Data) -> Result<Rating, SudokuError> {
+    rate_with(data, &Strategy::ALL).map(|(_, rating)| rating)
+}
+
+/// Rates a puzzle by the given strategies, returning its solution along with the rating.  The
+/// search is the slow part, so it runs once for both.
+pub fn rate_with(data: &Data, strategies: &[Strategy]) -> Result<(Data, Rating), SudokuError> {
+    let mut solver = Solver::new(data.clone())?;
+    solver.set_strategies(strategies);
+    let solution = solve_unique(data)?;
+
+    solver.solve()?;
+    Ok((solution, Rating::from_solver(&solver)))
+}
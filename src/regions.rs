@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::fs;
+
+use crate::{Geometry, Index, SudokuError};
+
+/// Reads a jigsaw region map: a character per cell naming its region, one row per line.
+///
+/// Any characters other than whitespace can name the regions, which are numbered in order of
+/// first appearance.  For example, a 4×4 layout:
+///
+/// ```text
+/// AABB
+/// ACCB
+/// ACDB
+/// CCDD
+/// ```
+pub fn parse_regions(s: &str) -> Result<Geometry, SudokuError> {
+    let mut names: HashMap<char, Index> = HashMap::new();
+    let mut regions = vec!();
+
+    for (n, line) in s.lines().enumerate() {
+        for (column, c) in line.chars().enumerate().filter(|(_, c)| !c.is_whitespace()) {
+            let next = names.len();
+            let region = *names.entry(c).or_insert(next);
+            if region >= crate::MAX_SIZE {
+                return Err(SudokuError::Parse {line: n + 1, column: column + 1, message: "too many regions".into()});
+            }
+            regions.push(region);
+        }
+    }
+    Geometry::jigsaw(regions)
+}
+
+pub fn read_regions(filename: &str) -> Result<Geometry, SudokuError> {
+    let content = fs::read_to_string(filename)?;
+    parse_regions(&content)
+}
+
+/// Writes the region map of a geometry, naming the regions `1`-`9` then `A`-`P`.
+pub fn write_regions(geometry: &Geometry) -> String {
+    geometry.regions().chunks(geometry.size()).map(|row| {
+        row.iter().map(|&region| geometry.digit_char(region as u8 + 1)).collect::<String>() + "\n"
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{Data, Possibles, Solver, Symmetry, Unit, minimize};
+    use crate::backtrack::{first_solution, solve_unique};
+
+    const REGIONS: &str = "
+        111222333
+        111222333
+        114222333
+        144555666
+        444555666
+        444558666
+        777588999
+        777888999
+        777888999
+    ";
+
+    #[test]
+    fn test_round_trip() {
+        let geometry = parse_regions(REGIONS).unwrap();
+
+        assert!(geometry.is_jigsaw());
+        assert_eq!(geometry.unit(Unit::Region, 0), &[0, 1, 2, 9, 10, 11, 18, 19, 27]);
+        assert_eq!(geometry.subsquare(0), geometry.unit(Unit::Region, 0));
+        assert_eq!(geometry.subsquare(4), &[30, 31, 32, 39, 40, 41, 48, 49, 57]);
+        assert_eq!(parse_regions(&write_regions(&geometry)).unwrap(), geometry);
+        assert!(parse_regions("AAB\nBBB\nCCC").is_err());
+    }
+
+    #[test]
+    fn test_locked_candidates() {
+        let geometry = parse_regions(REGIONS).unwrap();
+        let region = geometry.unit(Unit::Region, 0);
+
+        // Within region 0, 5 is only possible in row 2, so it is removed from the rest of the row
+        let cells = (0..81).map(|idx| {
+            let candidates = if region.contains(&idx) && idx != 18 && idx != 19 { vec!(6) } else { vec!(5, 6) };
+            (idx, HashSet::from_iter(candidates))
+        }).collect();
+        let mut possibles = Possibles::from_cells(cells);
+
+        assert_eq!(possibles.remove_overlays(&geometry), (20..27).map(|idx| (idx, 5)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_solve_jigsaw() {
+        let geometry = Arc::new(parse_regions(REGIONS).unwrap());
+        let solution = first_solution(&Data::with_geometry(geometry.clone())).unwrap();
+
+        assert!(crate::validate(&solution).is_empty());
+
+        let puzzle = minimize(&solution, Symmetry::Central).unwrap();
+        assert_eq!(solve_unique(&puzzle).unwrap(), solution);
+
+        let mut solver = Solver::new(puzzle).unwrap();
+        solver.solve().unwrap();
+        assert!(solver.data().iter().zip(solution.iter()).all(|(cell, value)| cell.is_none() || cell == value));
+    }
+}
@@ -0,0 +1,284 @@
+use crate::{Data, Rating, Solver, Step, Strategy, SudokuError, Violation};
+use crate::backtrack::solve_unique;
+use crate::formats::Snapshot;
+use crate::pencilmarks::write_grid;
+
+/// The version of the serialized report format.  It changes whenever a field is renamed,
+/// removed or changes meaning; new optional fields may be added within a version.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// The overall outcome of solving a puzzle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum Status {
+    Solved,
+    /// The givens break the rules
+    Invalid,
+    Unsolvable,
+    /// More than one solution, so the puzzle is not proper
+    Multiple,
+    /// Any other failure, such as unreadable input
+    Error
+}
+
+impl Status {
+    pub fn from_error(err: &SudokuError) -> Status {
+        match err {
+            SudokuError::Invalid(_) => Status::Invalid,
+            SudokuError::Unsolvable | SudokuError::Contradiction(_) => Status::Unsolvable,
+            SudokuError::MultipleSolutions => Status::Multiple,
+            _ => Status::Error
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Status::Solved => "solved",
+            Status::Invalid => "invalid",
+            Status::Unsolvable => "unsolvable",
+            Status::Multiple => "multiple",
+            Status::Error => "error"
+        }
+    }
+}
+
+/// Everything learned from solving one puzzle.
+///
+/// With the `serde` feature this serializes (as JSON, for instance) to an object with:
+///
+/// - `version`: the `FORMAT_VERSION` the report was written with
+/// - `puzzle`, `solution`: grids as one-line strings, a character per cell with `.` for blanks (`solution` may be null)
+/// - `status`: one of `solved`, `invalid`, `unsolvable`, `multiple` or `error`
+/// - `rating`: one of `easy`, `medium`, `hard` or `fiendish`, or null unless solved
+/// - `steps`: the logical deductions in order, each with a `strategy` (`naked_single`,
+///   `hidden_single`, `locked_candidates` and so on, see `Strategy`), a `placement` as
+///   `[index, value]` or null, and
+///   `eliminations` as a list of `[index, value]` candidates removed
+/// - `violations`: the rule violations of an invalid puzzle, see `Violation`
+/// - `error`: a message for any failure, or null
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SolveReport {
+    pub version: u32,
+    pub puzzle: Data,
+    pub status: Status,
+    pub solution: Option<Data>,
+    pub rating: Option<Rating>,
+    pub steps: Vec<Step>,
+    pub violations: Vec<Violation>,
+    pub error: Option<String>
+}
+
+impl SolveReport {
+    /// Validates, solves and rates a puzzle.
+    pub fn new(data: &Data) -> SolveReport {
+        SolveReport::with_strategies(data, &Strategy::ALL)
+    }
+
+    /// Validates, solves and rates a puzzle with only some of the logical strategies, which
+    /// makes for fewer steps and perhaps a harder rating.
+    pub fn with_strategies(data: &Data, strategies: &[Strategy]) -> SolveReport {
+        SolveReport::from_snapshot(&Snapshot::new(data.clone()), strategies)
+    }
+
+    /// Validates, solves and rates a solve in progress, such as imported pencilmarks, carrying
+    /// on from its grid and candidates.
+    pub fn from_snapshot(snapshot: &Snapshot, strategies: &[Strategy]) -> SolveReport {
+        let mut report = SolveReport {
+            version: FORMAT_VERSION,
+            puzzle: snapshot.givens.clone(),
+            status: Status::Solved,
+            solution: None,
+            rating: None,
+            steps: vec!(),
+            violations: vec!(),
+            error: None
+        };
+
+        let mut solver = match snapshot.clone().into_solver() {
+            Ok(solver) => solver,
+            Err(err) => return report.failed(err)
+        };
+        solver.set_strategies(strategies);
+        if let Err(err) = solver.solve() {
+            return report.failed(err)
+        }
+        report.steps = solver.steps().to_vec();
+
+        // Logical steps are sound, so the solution found from them is the puzzle's own
+        let solution = if solver.is_solved() { Ok(solver.data().clone()) } else { solve_unique(solver.data()) };
+        match solution {
+            Ok(solution) => {
+                report.solution = Some(solution);
+                report.rating = Some(Rating::from_solver(&solver));
+                report
+            },
+            Err(err) => report.failed(err)
+        }
+    }
+
+    fn failed(mut self, err: SudokuError) -> SolveReport {
+        self.status = Status::from_error(&err);
+        self.error = Some(err.to_string());
+        if let SudokuError::Invalid(violations) = err {
+            self.violations = violations;
+        }
+        self
+    }
+}
+
+/// The progress of a solver as text, for people watching it work: the candidates left at the
+/// start and after each pass of its last `solve`, the values it placed, and the pencilmarks of
+/// any cells still open.  The solver itself prints nothing.
+pub fn write_progress(solver: &Solver) -> String {
+    let passes = solver.passes();
+    let mut out = String::from("Solving\n");
+    if let Some(start) = passes.first() {
+        out += &format!("# possibles - start: {}\n", start.candidates);
+    }
+    for (number, pass) in passes.windows(2).enumerate() {
+        for step in &solver.steps()[pass[0].steps..pass[1].steps] {
+            if let Some((index, value)) = step.placement {
+                out += &format!("Updating solution [{}] = {}\n", index, value);
+            }
+        }
+        out += &format!("# possibles Pass: {}, Count: {}\n", number + 1, pass[1].candidates);
+    }
+    out += &format!("Finished after {} passes\n", passes.len().saturating_sub(1));
+    if !solver.possibles().is_empty() {
+        out += &write_grid(solver.data(), solver.possibles());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress() {
+        let data: Data = "..5....4...4..35.8.68..43.1...68..3...2...8...3..29...5.72..18.6.95..4...8....2..".parse().unwrap();
+        let mut solver = Solver::new(data).unwrap();
+        solver.solve().unwrap();
+        let progress = write_progress(&solver);
+        let lines: Vec<&str> = progress.lines().collect();
+
+        assert_eq!(lines[0], "Solving");
+        assert!(lines[1].starts_with("# possibles - start: "));
+        let blanks = solver.initial_data().iter().filter(|cell| cell.is_none()).count();
+        assert_eq!(lines.iter().filter(|line| line.starts_with("Updating solution")).count(), blanks);
+        assert_eq!(lines.last().unwrap(), &format!("Finished after {} passes", solver.passes().len() - 1));
+        assert_eq!(solver.passes().last().unwrap().candidates, 0);
+
+        // With naked singles only, a hard puzzle stalls and the open cells are shown
+        let data: Data = "4.....8.5.3..........7......2.....6.....8.4......1.......6.3.7.5..2.....1.4......".parse().unwrap();
+        let mut solver = Solver::new(data).unwrap();
+        solver.set_strategies(&[Strategy::NakedSingle]);
+        solver.solve().unwrap();
+        let report = SolveReport::with_strategies(solver.initial_data(), &[Strategy::NakedSingle]);
+        assert_eq!(report.steps, solver.steps());
+        assert!(!solver.is_solved());
+        assert_eq!(solver.passes().last().unwrap().candidates, solver.possibles().len());
+        assert!(!solver.possibles().is_empty());
+        assert!(write_progress(&solver).ends_with(&write_grid(solver.data(), solver.possibles())));
+    }
+
+    #[test]
+    fn test_report_from_snapshot() {
+        let data: Data = "..5....4...4..35.8.68..43.1...68..3...2...8...3..29...5.72..18.6.95..4...8....2..".parse().unwrap();
+        let mut snapshot = Snapshot::new(data.clone());
+        // With cell 0 down to its last candidate, a naked single places it first
+        for value in [1, 2, 7, 9] {
+            snapshot.possibles.update(&0, &value);
+        }
+
+        let report = SolveReport::from_snapshot(&snapshot, &[Strategy::NakedSingle]);
+        assert_eq!(report.status, Status::Solved);
+        assert_eq!(report.puzzle, data);
+        assert_eq!(report.steps[0].placement, Some((0, 3)));
+        assert_ne!(SolveReport::with_strategies(&data, &[Strategy::NakedSingle]).steps[0], report.steps[0]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_round_trip() {
+        let data: Data = "..5....4...4..35.8.68..43.1...68..3...2...8...3..29...5.72..18.6.95..4...8....2..".parse().unwrap();
+        let report = SolveReport::new(&data);
+        let json = serde_json::to_string(&report).unwrap();
+
+        assert!(json.starts_with(r#"{"version":2,"puzzle":"..5....4..."#));
+        assert!(json.contains(r#""status":"solved""#));
+        assert!(json.contains(r#""strategy":"naked_single""#));
+        assert_eq!(serde_json::from_str::<SolveReport>(&json).unwrap(), report);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_killer_jigsaw_round_trip() {
+        // Domino cages over a jigsaw, summing to a solution of the bare jigsaw
+        let geometry = crate::regions::parse_regions("
+            111222333
+            111222333
+            114222333
+            144555666
+            444555666
+            444558666
+            777588999
+            777888999
+            777888999
+        ").unwrap();
+        let solution = crate::backtrack::first_solution(&Data::with_geometry(std::sync::Arc::new(geometry.clone()))).unwrap();
+        let mut geometry = geometry.with_unit(crate::Unit::Diagonal, (0..9).map(|n| n * 10).collect()).unwrap();
+        for start in (0..81).filter(|idx| idx % 9 < 8 && idx % 2 == (idx / 9) % 2) {
+            let sum = (solution[start].unwrap() + solution[start + 1].unwrap()) as usize;
+            geometry = geometry.with_cage(crate::Cage::new(vec!(start, start + 1), sum, 9).unwrap()).unwrap();
+        }
+        let data = Data::parse_with(std::sync::Arc::new(geometry), &format!("{}{}", &solution.to_string()[..40], ".".repeat(41))).unwrap();
+        let report = SolveReport::new(&data);
+        let json = serde_json::to_string(&report).unwrap();
+        let read: SolveReport = serde_json::from_str(&json).unwrap();
+
+        assert!(json.contains(r#""rules":"cage "#));
+        assert_eq!(read.status, report.status);
+        assert_eq!(read.puzzle.to_string(), data.to_string());
+        assert_eq!(read.solution.map(|data| data.to_string()), report.solution.map(|data| data.to_string()));
+        assert_eq!(read.steps, report.steps);
+        let geometry = read.puzzle.geometry();
+        assert!(geometry.is_jigsaw());
+        assert_eq!(geometry.regions(), data.geometry().regions());
+        assert_eq!(geometry.unit(crate::Unit::Diagonal, 0), data.geometry().unit(crate::Unit::Diagonal, 0));
+        assert_eq!(geometry.constraints_of::<crate::Cage>().count(), 36);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_samurai_round_trip() {
+        let data = Data::with_geometry(std::sync::Arc::new(crate::Geometry::samurai()));
+        let json = serde_json::to_string(&data).unwrap();
+
+        assert_eq!(serde_json::from_str::<Data>(&json).unwrap(), data);
+        assert_eq!(serde_json::from_str::<Data>(r#""..5....4...4..35.8.68..43.1...68..3...2...8...3..29...5.72..18.6.95..4...8....2..""#).unwrap().len(), 81);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_invalid_report() {
+        let data: Data = "55...............................................................................".parse().unwrap();
+        let json = serde_json::to_value(SolveReport::new(&data)).unwrap();
+
+        assert_eq!(json["status"], "invalid");
+        assert_eq!(json["violations"][0]["conflict"]["unit"], "row");
+        assert_eq!(json["solution"], serde_json::Value::Null);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_possibles_round_trip() {
+        let data: Data = "1....7.9..3..2...8..96..5....53..9...1..8...26....4...3......1..4......7..7...3..".parse().unwrap();
+        let possibles = crate::Possibles::from_data(&data);
+        let json = serde_json::to_string(&possibles).unwrap();
+
+        assert!(json.starts_with(r#"{"1":[2,5,6,8],"2":[2,4,6,8],"#));
+        assert_eq!(serde_json::from_str::<crate::Possibles>(&json).unwrap(), possibles);
+    }
+}
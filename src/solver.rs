@@ -1,23 +1,105 @@
-use crate::{Data, Index, Value, Indices, Possibles};
+use std::sync::Arc;
+
+use itertools::sorted;
+
+use crate::{Data, Index, Value, Possibles, SudokuError, validate};
+use crate::constraint::remove_constraint_candidates;
+use crate::killer::remove_innies_outies;
+
+/// The logical strategies used by the solver, from easiest to hardest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum Strategy {
+    /// A cell with a single candidate left
+    NakedSingle,
+    /// A value with a single possible cell left in a row, column or subsquare
+    HiddenSingle,
+    /// A value confined to one row or column of a subsquare (or the reverse)
+    LockedCandidates,
+    /// A candidate ruled out by one of the constraints of the geometry, such as a killer cage
+    /// which no set of digits making its sum can use, or a dot, line or outside clue
+    Constraints,
+    /// A candidate out of reach of the sum left for the innies or outies of a unit
+    InniesOuties
+}
+
+impl Strategy {
+    /// Every strategy, from easiest to hardest.
+    pub const ALL: [Strategy; 5] = [
+        Strategy::NakedSingle, Strategy::HiddenSingle, Strategy::LockedCandidates, Strategy::Constraints,
+        Strategy::InniesOuties
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Strategy::NakedSingle => "naked-single",
+            Strategy::HiddenSingle => "hidden-single",
+            Strategy::LockedCandidates => "locked-candidates",
+            Strategy::Constraints => "constraints",
+            Strategy::InniesOuties => "innies-outies"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Strategy> {
+        Strategy::ALL.into_iter().find(|strategy| strategy.name() == name)
+    }
+}
+
+/// One deduction made while solving: either a placed value, or a set of eliminated candidates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Step {
+    pub strategy: Strategy,
+    pub placement: Option<(Index, Value)>,
+    pub eliminations: Vec<(Index, Value)>
+}
+
+/// Where `solve` stood at its start or after one of its passes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pass {
+    /// The number of steps made so far
+    pub steps: usize,
+    /// The number of candidates left
+    pub candidates: usize
+}
+
+#[derive(Clone)]
 pub struct Solver {
     initial_data: Data,
     data: Data,
-    possibles: Possibles
+    possibles: Possibles,
+    steps: Vec<Step>,
+    strategies: Vec<Strategy>,
+    passes: Vec<Pass>
 }
 
 impl Solver {
-    pub fn new(data: Data) -> Solver {
+    /// Fails with the list of violations when the grid breaks the rules before solving starts.
+    pub fn new(data: Data) -> Result<Solver, SudokuError> {
+        let violations = validate(&data);
+        if !violations.is_empty() {
+            return Err(SudokuError::Invalid(violations));
+        }
+
         let initial_data = data.clone();
-        let possibles = Possibles::from_data(data);
-        Solver {initial_data, data, possibles}
+        let possibles = Possibles::from_data(&data);
+        Ok(Solver {initial_data, data, possibles, steps: vec!(), strategies: Strategy::ALL.to_vec(), passes: vec!()})
     }
 
-    pub fn clone(&self) -> Solver {
-        Solver {
-            initial_data: self.initial_data.clone(), 
-            data: self.data.clone(), 
-            possibles: self.possibles.clone()
+    /// Resumes solving from a saved state: the original givens, the grid so far and its
+    /// candidates, such as imported pencilmarks.
+    pub fn from_state(initial_data: Data, data: Data, possibles: Possibles) -> Result<Solver, SudokuError> {
+        let violations = validate(&data);
+        if !violations.is_empty() {
+            return Err(SudokuError::Invalid(violations));
         }
+        Ok(Solver {initial_data, data, possibles, steps: vec!(), strategies: Strategy::ALL.to_vec(), passes: vec!()})
+    }
+
+    /// Limits the solver to some of the strategies, such as only the singles.  All of them are
+    /// used unless set.
+    pub fn set_strategies(&mut self, strategies: &[Strategy]) {
+        self.strategies = strategies.to_vec();
     }
 
     pub fn initial_data(&self) -> &Data {
@@ -28,63 +110,241 @@ impl Solver {
         &self.data
     }
 
+    pub fn possibles(&self) -> &Possibles {
+        &self.possibles
+    }
+
+    /// The deductions made so far, in order.
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// The progress of the last `solve`: where it stood at the start and after each pass.  See
+    /// `write_progress` for these as text.
+    pub fn passes(&self) -> &[Pass] {
+        &self.passes
+    }
+
+    pub fn is_solved(&self) -> bool {
+        self.data.iter().all(|item| item.is_some())
+    }
+
     fn remove_single_possibles(&mut self) {
         // Checks for any single possibles in the possibles map
 
-        for (index, value) in self.possibles.find_singles() {
-            self.update(index, value);
+        for (index, value) in sorted(self.possibles.find_singles()) {
+            self.update(index, value, Strategy::NakedSingle);
         }
     }
 
     fn remove_single_values(&mut self) {
         // For each row, column, subsquare, check for any single possible values
 
-        for (index, value) in self.possibles.find_single_values() {
-            self.update(index, value);
+        for (index, value) in sorted(self.possibles.find_single_values(self.data.geometry())) {
+            self.update(index, value, Strategy::HiddenSingle);
         }
-        
+
     }
 
-    fn update(&mut self, index: Index, value: Value) {
-        if let Some(_) = self.data[index] {return};
+    fn remove_overlays(&mut self) {
+        let eliminations = self.possibles.remove_overlays(self.data.geometry());
+        if !eliminations.is_empty() {
+            self.steps.push(Step {strategy: Strategy::LockedCandidates, placement: None, eliminations});
+        }
+    }
+
+    fn remove_constraint_candidates(&mut self) {
+        let eliminations = remove_constraint_candidates(&self.data, &mut self.possibles);
+        if !eliminations.is_empty() {
+            self.steps.push(Step {strategy: Strategy::Constraints, placement: None, eliminations});
+        }
+    }
+
+    fn remove_innies_outies(&mut self) {
+        let eliminations = remove_innies_outies(&self.data, &mut self.possibles);
+        if !eliminations.is_empty() {
+            self.steps.push(Step {strategy: Strategy::InniesOuties, placement: None, eliminations});
+        }
+    }
+
+    fn update(&mut self, index: Index, value: Value, strategy: Strategy) {
+        if self.data[index].is_some() {return};
 
-        println!("Updating solution [{}] = {}", index, value);
         self.data[index] = Some(value);
         self.possibles.remove(&index);
 
-        for idx in Indices::around(index) {
-            self.possibles.update(&idx, &value);
+        let geometry = Arc::clone(self.data.geometry());
+        let mut eliminations = vec!();
+        for &idx in geometry.around(index) {
+            if self.possibles.update(&idx, &value) {
+                eliminations.push((idx, value));
+            }
         }
+        self.steps.push(Step {strategy, placement: Some((index, value)), eliminations});
     }
 
-    pub fn solve(&mut self) {
-        println!("Solving");
-        
-        let mut _possibles_count = self.possibles.len();
-        let mut count: usize = 0;
-        println!("# possibles - start: {}", self.possibles.len());
+    /// Applies the logical strategies until they make no further progress.
+    ///
+    /// The grid may be left incomplete, but fails if a cell runs out of candidates.
+    pub fn solve(&mut self) -> Result<(), SudokuError> {
+        self.passes.clear();
+        self.record_pass();
         loop {
-            self.remove_single_possibles();
-            self.remove_single_values();
-            self.possibles.remove_overlays();
-            
-            count += 1;
-            
-            println!("# possibles Pass: {}, Count: {}", count, self.possibles.len());
-            
-            if self.possibles.len() == 0 || self.possibles.len() == _possibles_count {
+            let progress = self.steps.len();
+            self.apply_strategies();
+
+            if let Some(index) = self.possibles.find_empty() {
+                return Err(SudokuError::Contradiction(index));
+            }
+
+            self.record_pass();
+
+            if self.possibles.is_empty() || self.steps.len() == progress {
                 break
             }
-            _possibles_count = self.possibles.len();
         }
 
-        println!("Finished after {} passes", count);
-        if self.possibles.len() != 0 {
-            self.possibles.print();
+        Ok(())
+    }
+
+    /// Applies the strategies only until one of them makes progress, and returns the first
+    /// step made, or `None` when they are stuck.  A contradiction which the step leads to is
+    /// left for later steps to find.
+    pub fn next_step(&mut self) -> Result<Option<&Step>, SudokuError> {
+        let progress = self.steps.len();
+        self.apply_strategies();
+        if self.steps.len() == progress {
+            if let Some(index) = self.possibles.find_empty() {
+                return Err(SudokuError::Contradiction(index));
+            }
+        }
+        Ok(self.steps.get(progress))
+    }
 
-            //println!("{:?}", self.possibles.window(Indices::subsquare(8)));
+    fn apply_strategies(&mut self) {
+        // Only fall back on a harder strategy when the easier ones make no progress
+        let progress = self.steps.len();
+        for strategy in Strategy::ALL {
+            if self.steps.len() != progress {
+                break
+            }
+            if !self.strategies.contains(&strategy) {
+                continue
+            }
+            match strategy {
+                Strategy::NakedSingle => self.remove_single_possibles(),
+                Strategy::HiddenSingle => self.remove_single_values(),
+                Strategy::LockedCandidates => self.remove_overlays(),
+                Strategy::Constraints => self.remove_constraint_candidates(),
+                Strategy::InniesOuties => self.remove_innies_outies()
+            }
         }
-        
     }
 
-}
\ No newline at end of file
+    fn record_pass(&mut self) {
+        self.passes.push(Pass {steps: self.steps.len(), candidates: self.possibles.len()});
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Geometry, Relation, Unit};
+    use crate::backtrack::first_solution;
+
+    #[test]
+    fn test_other_sizes() {
+        let data: Data = ".23456 45.123 231.64 5642.1 31264. 64531.".parse().unwrap();
+        let mut solver = Solver::new(data).unwrap();
+        solver.solve().unwrap();
+        assert!(solver.is_solved());
+        assert_eq!(solver.data().to_string(), "123456456123231564564231312645645312");
+
+        let empty = Data::with_geometry(Geometry::for_size(16).unwrap());
+        let solution = first_solution(&empty).unwrap();
+        assert_eq!(solution.count(), 256);
+        assert!(validate(&solution).is_empty());
+    }
+
+    #[test]
+    fn test_extra_units() {
+        let geometry = Arc::new(Geometry::new(3, 3).unwrap().with_diagonals().with_windows());
+        let solution = first_solution(&Data::with_geometry(geometry.clone())).unwrap();
+        let diagonal: Vec<Value> = geometry.unit(Unit::Diagonal, 1).iter().map(|&idx| solution[idx].unwrap()).collect();
+        assert_eq!(sorted(diagonal).collect::<Vec<Value>>(), (1..=9).collect::<Vec<Value>>());
+
+        // A single given on a diagonal rules its digit out along the whole diagonal
+        let mut data = Data::with_geometry(geometry);
+        data[0] = Some(1);
+        let solver = Solver::new(data).unwrap();
+        assert!(!solver.possibles().candidates(&80).unwrap().contains(&1));
+        assert!(solver.possibles().candidates(&71).unwrap().contains(&1));
+    }
+
+    #[test]
+    fn test_strategies() {
+        let data: Data = "..5....4...4..35.8.68..43.1...68..3...2...8...3..29...5.72..18.6.95..4...8....2..".parse().unwrap();
+        let mut solver = Solver::new(data).unwrap();
+        solver.set_strategies(&[Strategy::HiddenSingle]);
+        solver.solve().unwrap();
+        assert!(!solver.steps().is_empty());
+        assert!(solver.steps().iter().all(|step| step.strategy == Strategy::HiddenSingle));
+        assert_eq!(Strategy::from_name("innies-outies"), Some(Strategy::InniesOuties));
+        assert!(Strategy::ALL.iter().all(|strategy| Strategy::from_name(strategy.name()) == Some(*strategy)));
+    }
+
+    #[test]
+    fn test_next_step() {
+        // The first single is sound, but the ones after it run a cell out of digits
+        let data: Data = ".32...1.3......3".parse().unwrap();
+        let mut solver = Solver::new(data.clone()).unwrap();
+        let step = solver.next_step().unwrap().cloned().unwrap();
+        assert_eq!((step.strategy, step.placement), (Strategy::NakedSingle, Some((3, 4))));
+        assert!(matches!(solver.solve(), Err(SudokuError::Contradiction(_))));
+
+        let mut solver = Solver::new(data).unwrap();
+        solver.set_strategies(&[]);
+        assert_eq!(solver.next_step().unwrap(), None);
+    }
+
+    #[test]
+    fn test_relations() {
+        let geometry = Arc::new(Geometry::new(3, 3).unwrap().with_relation(Relation::AntiKnight).with_relation(Relation::AntiKing));
+        let solution = first_solution(&Data::with_geometry(geometry.clone())).unwrap();
+        assert!(validate(&solution).is_empty());
+
+        let mut data = Data::with_geometry(geometry);
+        data[40] = Some(3);
+        let solver = Solver::new(data).unwrap();
+        assert!(!solver.possibles().candidates(&23).unwrap().contains(&3));
+        assert!(solver.possibles().candidates(&24).unwrap().contains(&3));
+    }
+
+    #[test]
+    fn test_samurai() {
+        let geometry = Arc::new(Geometry::samurai());
+        let solution = first_solution(&Data::with_geometry(geometry.clone())).unwrap();
+        assert!(validate(&solution).is_empty());
+
+        // A digit in a shared box rules itself out of the rows of both grids
+        let mut data = Data::with_geometry(geometry.clone());
+        data[geometry.index_at(6, 6).unwrap()] = Some(5);
+        let solver = Solver::new(data).unwrap();
+        for (row, column) in [(6, 0), (6, 14), (14, 6)] {
+            assert!(!solver.possibles().candidates(&geometry.index_at(row, column).unwrap()).unwrap().contains(&5));
+        }
+
+        // The shared boxes are left empty, to be filled from the grids around them
+        let mut data = solution.clone();
+        for idx in 0..data.len() {
+            if geometry.units().filter(|(unit, _, idxs)| *unit == Unit::Row && idxs.contains(&idx)).count() > 1 {
+                data[idx] = None;
+            }
+        }
+        assert_eq!(data.iter().filter(|item| item.is_none()).count(), 36);
+        let mut solver = Solver::new(data).unwrap();
+        solver.solve().unwrap();
+        assert_eq!(solver.data(), &solution);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::{Data, Geometry, Index, Value, Possibles, Step, Unit};
+
+/// What to draw on top of the grid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgOptions {
+    /// Width and height of a cell, in SVG user units
+    pub cell_size: f64,
+    /// Draw the candidates of unsolved cells
+    pub pencilmarks: bool,
+    /// Cells given a highlighted background
+    pub highlight_cells: Vec<Index>,
+    /// Candidates circled as the reason for a step, or around the digit once placed
+    pub highlight_candidates: Vec<(Index, Value)>,
+    /// Candidates drawn struck out in red, even when already removed from the possibles
+    pub eliminated_candidates: Vec<(Index, Value)>
+}
+
+impl Default for SvgOptions {
+    fn default() -> SvgOptions {
+        SvgOptions {
+            cell_size: 50.0,
+            pencilmarks: false,
+            highlight_cells: vec!(),
+            highlight_candidates: vec!(),
+            eliminated_candidates: vec!()
+        }
+    }
+}
+
+impl SvgOptions {
+    /// Highlights a solving step: the placed cell and the candidates it eliminated.
+    pub fn with_step(mut self, step: &Step) -> SvgOptions {
+        if let Some((index, value)) = step.placement {
+            self.highlight_cells.push(index);
+            self.highlight_candidates.push((index, value));
+        }
+        self.eliminated_candidates.extend(step.eliminations.iter().copied());
+        self
+    }
+}
+
+const GIVEN_COLOUR: &str = "#000000";
+const SOLVED_COLOUR: &str = "#1f4fbf";
+const PENCILMARK_COLOUR: &str = "#606060";
+const HIGHLIGHT_FILL: &str = "#fff3a0";
+const CANDIDATE_FILL: &str = "#a8e6a0";
+const ELIMINATED_COLOUR: &str = "#d02020";
+const EXTRA_UNIT_COLOUR: &str = "#d8d8d8";
+
+// The top left corner of a cell.
+fn cell_corner(geometry: &Geometry, idx: Index, size: f64, margin: f64) -> (f64, f64) {
+    let position = geometry.position_from_index(idx);
+    (margin + position.column() as f64 * size, margin + position.row() as f64 * size)
+}
+
+// The centre of a candidate's slot within its cell, laid out like a keypad in the shape of a box.
+fn candidate_centre(geometry: &Geometry, idx: Index, value: Value, size: f64, margin: f64) -> (f64, f64) {
+    let (left, top) = cell_corner(geometry, idx, size, margin);
+    let (columns, rows) = (geometry.box_width(), geometry.box_height());
+    let slot = (value - 1) as usize;
+    let x = left + ((slot % columns) as f64 + 0.5) * size / columns as f64;
+    let y = top + ((slot / columns) as f64 + 0.5) * size / rows as f64;
+    (x, y)
+}
+
+/// Renders a grid as a standalone SVG document.
+///
+/// Cells filled in `givens` are drawn in black and the other filled cells of `data` in blue.
+/// With `possibles` and `options.pencilmarks`, unsolved cells show their candidates.
+pub fn render_svg(givens: &Data, data: &Data, possibles: Option<&Possibles>, options: &SvgOptions) -> String {
+    let geometry = data.geometry();
+    let size = options.cell_size;
+    let margin = size / 10.0;
+    let width = geometry.width() as f64 * size + 2.0 * margin;
+    let height = geometry.height() as f64 * size + 2.0 * margin;
+    let font_scale = 1.0 / geometry.box_width().max(geometry.box_height()) as f64;
+    let mut elements: Vec<String> = vec!();
+
+    elements.push(format!(r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#, w = width, h = height));
+    elements.push(format!(r##"<rect x="0" y="0" width="{w}" height="{h}" fill="#ffffff"/>"##, w = width, h = height));
+
+    // Windows are shaded and diagonals drawn corner to corner
+    for (unit, _, idxs) in geometry.units() {
+        match unit {
+            Unit::Diagonal => {
+                let (first, last) = (cell_corner(geometry, idxs[0], size, margin), cell_corner(geometry, idxs[idxs.len() - 1], size, margin));
+                let (x1, x2) = if first.0 < last.0 { (first.0, last.0 + size) } else { (first.0 + size, last.0) };
+                elements.push(format!(r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="{}"/>"#,
+                    x1, first.1, x2, last.1 + size, EXTRA_UNIT_COLOUR, size / 16.0
+                ));
+            },
+            Unit::Window | Unit::Extra => for &idx in idxs {
+                let (x, y) = cell_corner(geometry, idx, size, margin);
+                elements.push(format!(r#"<rect x="{}" y="{}" width="{s}" height="{s}" fill="{}"/>"#, x, y, EXTRA_UNIT_COLOUR, s = size));
+            },
+            _ => {}
+        }
+    }
+
+    for &idx in options.highlight_cells.iter() {
+        let (x, y) = cell_corner(geometry, idx, size, margin);
+        elements.push(format!(r#"<rect x="{}" y="{}" width="{s}" height="{s}" fill="{}"/>"#, x, y, HIGHLIGHT_FILL, s = size));
+    }
+
+    let line = |x1: f64, y1: f64, x2: f64, y2: f64, stroke: f64| format!(
+        r##"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="#000000" stroke-width="{}" stroke-linecap="square"/>"##,
+        x1, y1, x2, y2, stroke
+    );
+    // Thin lines between cells of the same subsquare or region, and thick lines between those
+    // of different ones and around the edges of the grids, drawn as runs along each boundary
+    let (rows, columns) = (geometry.height(), geometry.width());
+    let (thin, thick) = (size / 50.0, size / 16.0);
+    let edge = |a: Option<Index>, b: Option<Index>| match (a, b) {
+        (Some(a), Some(b)) => Some(if geometry.regions()[a] == geometry.regions()[b] { thin } else { thick }),
+        (Some(_), None) | (None, Some(_)) => Some(thick),
+        (None, None) => None
+    };
+    let mut draw = |edges: Vec<Option<f64>>, at: &dyn Fn(usize) -> (f64, f64, f64, f64)| {
+        let mut start = 0;
+        for k in 1..=edges.len() {
+            if k == edges.len() || edges[k] != edges[start] {
+                if let Some(stroke) = edges[start] {
+                    let (x1, y1, _, _) = at(start);
+                    let (_, _, x2, y2) = at(k - 1);
+                    elements.push(line(x1, y1, x2, y2, stroke));
+                }
+                start = k;
+            }
+        }
+    };
+    for row in 0..=rows {
+        let y = margin + row as f64 * size;
+        let above = |column: usize| row.checked_sub(1).and_then(|row| geometry.index_at(row, column));
+        let edges = (0..columns).map(|column| edge(above(column), geometry.index_at(row, column))).collect();
+        draw(edges, &|column| (margin + column as f64 * size, y, margin + (column + 1) as f64 * size, y));
+    }
+    for column in 0..=columns {
+        let x = margin + column as f64 * size;
+        let left = |row: usize| column.checked_sub(1).and_then(|column| geometry.index_at(row, column));
+        let edges = (0..rows).map(|row| edge(left(row), geometry.index_at(row, column))).collect();
+        draw(edges, &|row| (x, margin + row as f64 * size, x, margin + (row + 1) as f64 * size));
+    }
+
+    // Circled under the digits: a candidate in its slot, or a placed one around the whole digit
+    for &(idx, value) in options.highlight_candidates.iter() {
+        let (x, y, r) = match data[idx] {
+            Some(placed) if placed == value => {
+                let (left, top) = cell_corner(geometry, idx, size, margin);
+                (left + size / 2.0, top + size / 2.0, size * 0.4)
+            },
+            Some(_) => continue,
+            None => {
+                let (x, y) = candidate_centre(geometry, idx, value, size, margin);
+                (x, y, size * font_scale * 3.0 / 7.0)
+            }
+        };
+        elements.push(format!(r#"<circle cx="{}" cy="{}" r="{}" fill="{}"/>"#, x, y, r, CANDIDATE_FILL));
+    }
+
+    for (idx, item) in data.iter().enumerate() {
+        if let Some(value) = item {
+            let colour = if givens[idx].is_some() { GIVEN_COLOUR } else { SOLVED_COLOUR };
+            let weight = if givens[idx].is_some() { "bold" } else { "normal" };
+            let (left, top) = cell_corner(geometry, idx, size, margin);
+            elements.push(format!(r#"<text x="{}" y="{}" font-family="sans-serif" font-size="{}" font-weight="{}" fill="{}" text-anchor="middle" dominant-baseline="central">{}</text>"#,
+                left + size / 2.0, top + size / 2.0, size * 0.7, weight, colour, geometry.digit_char(*value)
+            ));
+        }
+    }
+
+    if let (Some(possibles), true) = (possibles, options.pencilmarks) {
+        for idx in (0..data.len()).filter(|&i| data[i].is_none()) {
+            for &value in possibles.candidates(&idx).into_iter().flatten() {
+                if options.eliminated_candidates.contains(&(idx, value)) {
+                    continue
+                }
+                let (x, y) = candidate_centre(geometry, idx, value, size, margin);
+                elements.push(format!(r#"<text x="{}" y="{}" font-family="sans-serif" font-size="{}" fill="{}" text-anchor="middle" dominant-baseline="central">{}</text>"#,
+                    x, y, size * 0.75 * font_scale, PENCILMARK_COLOUR, geometry.digit_char(value)
+                ));
+            }
+        }
+    }
+
+    for &(idx, value) in options.eliminated_candidates.iter() {
+        let (x, y) = candidate_centre(geometry, idx, value, size, margin);
+        let half = size * font_scale / 3.0;
+        elements.push(format!(r#"<text x="{}" y="{}" font-family="sans-serif" font-size="{}" fill="{}" text-anchor="middle" dominant-baseline="central">{}</text>"#,
+            x, y, size * 0.75 * font_scale, ELIMINATED_COLOUR, geometry.digit_char(value)
+        ));
+        elements.push(format!(r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="{}"/>"#,
+            x - half, y + half, x + half, y - half, ELIMINATED_COLOUR, size / 40.0
+        ));
+    }
+
+    elements.push("</svg>\n".into());
+    elements.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Solver;
+
+    #[test]
+    fn test_render_step() {
+        let givens: Data = "..5....4...4..35.8.68..43.1...68..3...2...8...3..29...5.72..18.6.95..4...8....2..".parse().unwrap();
+        let mut solver = Solver::new(givens.clone()).unwrap();
+        solver.solve().unwrap();
+
+        let step = &solver.steps()[0];
+        let options = SvgOptions {pencilmarks: true, ..SvgOptions::default()}.with_step(step);
+        let svg = render_svg(&givens, solver.data(), Some(solver.possibles()), &options);
+
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+        assert!(svg.contains(HIGHLIGHT_FILL));
+        // The placed digit is circled in its cell, now filled in
+        let (index, _) = step.placement.unwrap();
+        let (left, top) = cell_corner(givens.geometry(), index, 50.0, 5.0);
+        let circle = format!(r#"<circle cx="{}" cy="{}" r="20" fill="{}"/>"#, left + 25.0, top + 25.0, CANDIDATE_FILL);
+        assert_eq!(svg.matches("<circle").count(), 1);
+        assert!(svg.contains(&circle));
+        assert_eq!(svg.matches(SOLVED_COLOUR).count(), 81 - givens.count());
+        assert_eq!(svg.matches(ELIMINATED_COLOUR).count(), 2 * step.eliminations.len());
+    }
+}
@@ -0,0 +1,511 @@
+use crate::{Data, Index, Value, SudokuError};
+use crate::random::Rng;
+
+/// An axis to reflect the board across.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    /// The middle row, swapping top and bottom
+    Horizontal,
+    /// The middle column, swapping left and right
+    Vertical,
+    /// The top left to bottom right diagonal
+    Diagonal,
+    /// The top right to bottom left diagonal
+    AntiDiagonal
+}
+
+/// An element of the validity preserving transformation group of the board.
+///
+/// The positional part (transpose, row and column permutations) has 3,359,232 elements:
+/// 2 for the transpose, 6^4 for the rows (band order and the order within each band) and
+/// 6^4 for the columns.  The digit relabeling multiplies this by another 9!.
+///
+/// Applying a transformation builds cell `(r, c)` of the result from cell
+/// `(rows[r], columns[c])` of the source, after transposing the source if requested,
+/// and then relabels the digit through `digits`.  Transformations act on 9×9 grids only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transformation {
+    transpose: bool,
+    rows: [Index; 9],
+    columns: [Index; 9],
+    digits: [Value; 10]
+}
+
+// A permutation of 0..9 which keeps each group of three within the same band (or stack).
+fn is_band_permutation(perm: &[Index; 9]) -> bool {
+    let mut seen = [false; 9];
+    for &i in perm.iter() {
+        if i >= 9 || seen[i] {
+            return false
+        }
+        seen[i] = true;
+    }
+    perm.chunks(3).all(|band| band.iter().all(|&i| i / 3 == band[0] / 3))
+}
+
+fn is_digit_permutation(digits: &[Value; 10]) -> bool {
+    let mut seen = [false; 10];
+    for &d in digits[1..].iter() {
+        if d == 0 || d > 9 || seen[d as usize] {
+            return false
+        }
+        seen[d as usize] = true;
+    }
+    digits[0] == 0
+}
+
+const PERMUTATIONS_3: [[Index; 3]; 6] = [
+    [0, 1, 2], [0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]
+];
+
+const REVERSED: [Index; 9] = [8, 7, 6, 5, 4, 3, 2, 1, 0];
+
+fn swap_groups(a: Index, b: Index) -> Result<[Index; 9], SudokuError> {
+    if a >= 3 || b >= 3 {
+        return Err(SudokuError::IndexOutOfRange(a.max(b)))
+    }
+    let mut perm = Transformation::identity().rows;
+    for k in 0..3 {
+        perm.swap(3 * a + k, 3 * b + k);
+    }
+    Ok(perm)
+}
+
+fn permute_group(group: Index, order: [Index; 3]) -> Result<[Index; 9], SudokuError> {
+    if group >= 3 {
+        return Err(SudokuError::IndexOutOfRange(group))
+    }
+    if !PERMUTATIONS_3.contains(&order) {
+        return Err(SudokuError::InvalidPermutation)
+    }
+    let mut perm = Transformation::identity().rows;
+    for k in 0..3 {
+        perm[3 * group + k] = 3 * group + order[k];
+    }
+    Ok(perm)
+}
+
+fn random_band_permutation(rng: &mut Rng) -> [Index; 9] {
+    let mut bands = [0, 1, 2];
+    rng.shuffle(&mut bands);
+    let mut perm = [0; 9];
+    for (slot, band) in bands.iter().enumerate() {
+        let mut within = [0, 1, 2];
+        rng.shuffle(&mut within);
+        for k in 0..3 {
+            perm[3 * slot + k] = 3 * band + within[k];
+        }
+    }
+    perm
+}
+
+// All 6^4 permutations of 0..9 which keep rows within bands.
+fn band_permutations() -> Vec<[Index; 9]> {
+    let mut perms = Vec::with_capacity(1296);
+    for bands in PERMUTATIONS_3 {
+        for first in PERMUTATIONS_3 {
+            for second in PERMUTATIONS_3 {
+                for third in PERMUTATIONS_3 {
+                    let mut perm = [0; 9];
+                    for (slot, within) in [first, second, third].iter().enumerate() {
+                        for k in 0..3 {
+                            perm[3 * slot + k] = 3 * bands[slot] + within[k];
+                        }
+                    }
+                    perms.push(perm);
+                }
+            }
+        }
+    }
+    perms
+}
+
+impl Transformation {
+    /// Builds a transformation, failing unless `rows` and `columns` keep lines within their
+    /// bands and stacks and `digits` is a permutation of 1..=9 (with `digits[0] == 0`).
+    pub fn new(transpose: bool, rows: [Index; 9], columns: [Index; 9], digits: [Value; 10]) -> Result<Transformation, SudokuError> {
+        if is_band_permutation(&rows) && is_band_permutation(&columns) && is_digit_permutation(&digits) {
+            Ok(Transformation {transpose, rows, columns, digits})
+        } else {
+            Err(SudokuError::InvalidPermutation)
+        }
+    }
+
+    pub fn identity() -> Transformation {
+        Transformation {
+            transpose: false,
+            rows: [0, 1, 2, 3, 4, 5, 6, 7, 8],
+            columns: [0, 1, 2, 3, 4, 5, 6, 7, 8],
+            digits: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]
+        }
+    }
+
+    /// Clockwise rotation by the given number of quarter turns.
+    pub fn rotation(quarter_turns: usize) -> Transformation {
+        let identity = Transformation::identity();
+        let reversed = REVERSED;
+        let (transpose, rows, columns) = match quarter_turns % 4 {
+            0 => (false, identity.rows, identity.columns),
+            1 => (true, identity.rows, reversed),
+            2 => (false, reversed, reversed),
+            _ => (true, reversed, identity.columns)
+        };
+        Transformation {transpose, rows, columns, digits: identity.digits}
+    }
+
+    pub fn reflection(axis: Axis) -> Transformation {
+        let identity = Transformation::identity();
+        let (transpose, rows, columns) = match axis {
+            Axis::Horizontal => (false, REVERSED, identity.columns),
+            Axis::Vertical => (false, identity.rows, REVERSED),
+            Axis::Diagonal => (true, identity.rows, identity.columns),
+            Axis::AntiDiagonal => (true, REVERSED, REVERSED)
+        };
+        Transformation {transpose, rows, columns, digits: identity.digits}
+    }
+
+    pub fn transposition() -> Transformation {
+        Transformation::reflection(Axis::Diagonal)
+    }
+
+    /// Relabels digit `d` as `digits[d]`, failing unless `digits` is a permutation.
+    pub fn relabeling(digits: [Value; 10]) -> Result<Transformation, SudokuError> {
+        let identity = Transformation::identity();
+        Transformation::new(false, identity.rows, identity.columns, digits)
+    }
+
+    /// Swaps two bands (groups of three rows), failing for bands outside 0..3.
+    pub fn band_swap(a: Index, b: Index) -> Result<Transformation, SudokuError> {
+        let mut transformation = Transformation::identity();
+        transformation.rows = swap_groups(a, b)?;
+        Ok(transformation)
+    }
+
+    /// Swaps two stacks (groups of three columns), failing for stacks outside 0..3.
+    pub fn stack_swap(a: Index, b: Index) -> Result<Transformation, SudokuError> {
+        let mut transformation = Transformation::identity();
+        transformation.columns = swap_groups(a, b)?;
+        Ok(transformation)
+    }
+
+    /// Reorders the rows of a band, so row `k` of the band takes the old row `order[k]`.
+    pub fn row_permutation(band: Index, order: [Index; 3]) -> Result<Transformation, SudokuError> {
+        let mut transformation = Transformation::identity();
+        transformation.rows = permute_group(band, order)?;
+        Ok(transformation)
+    }
+
+    /// Reorders the columns of a stack, so column `k` of the stack takes the old column `order[k]`.
+    pub fn column_permutation(stack: Index, order: [Index; 3]) -> Result<Transformation, SudokuError> {
+        let mut transformation = Transformation::identity();
+        transformation.columns = permute_group(stack, order)?;
+        Ok(transformation)
+    }
+
+    /// A transformation drawn uniformly from the whole group, reproducible from `seed`.
+    pub fn random(seed: u64) -> Transformation {
+        let mut rng = Rng::new(seed);
+        let transpose = rng.coin();
+        let rows = random_band_permutation(&mut rng);
+        let columns = random_band_permutation(&mut rng);
+
+        let mut digits = Transformation::identity().digits;
+        rng.shuffle(&mut digits[1..]);
+        Transformation {transpose, rows, columns, digits}
+    }
+
+    /// Every positional transformation (without digit relabeling), 3,359,232 in all.
+    pub fn positional() -> impl Iterator<Item = Transformation> {
+        let perms = band_permutations();
+        let digits = Transformation::identity().digits;
+
+        [false, true].into_iter().flat_map(move |transpose| {
+            let perms = perms.clone();
+            perms.clone().into_iter().flat_map(move |rows| {
+                perms.clone().into_iter().map(move |columns| Transformation {transpose, rows, columns, digits})
+            })
+        })
+    }
+
+    // The source cell feeding cell `idx` of the result.
+    fn source(&self, idx: Index) -> Index {
+        let (row, column) = (self.rows[idx / 9], self.columns[idx % 9]);
+        if self.transpose {
+            9 * column + row
+        } else {
+            9 * row + column
+        }
+    }
+
+    /// Transforms a grid, failing for grids other than 9×9.
+    pub fn apply(&self, data: &Data) -> Result<Data, SudokuError> {
+        if !data.geometry().is_classic() {
+            return Err(SudokuError::InvalidGeometry("transformations apply to classic 9x9 grids only".into()));
+        }
+
+        let mut out = Data::empty();
+        for (idx, item) in out.iter_mut().enumerate() {
+            *item = data[self.source(idx)].map(|v| self.digits[v as usize]);
+        }
+        Ok(out)
+    }
+
+    /// The transformation equivalent to applying `self` and then `other`.
+    pub fn then(&self, other: &Transformation) -> Transformation {
+        // Compose through the cell mapping, then split it back into its parts.
+        let sources: Vec<Index> = (0..81).map(|idx| self.source(other.source(idx))).collect();
+        let transpose = sources[1] % 9 == sources[0] % 9;
+        let (mut rows, mut columns) = ([0; 9], [0; 9]);
+        for k in 0..9 {
+            let (row_source, column_source) = (sources[9 * k], sources[k]);
+            if transpose {
+                rows[k] = row_source % 9;
+                columns[k] = column_source / 9;
+            } else {
+                rows[k] = row_source / 9;
+                columns[k] = column_source % 9;
+            }
+        }
+
+        let mut digits = [0; 10];
+        for (d, digit) in digits.iter_mut().enumerate().skip(1) {
+            *digit = other.digits[self.digits[d] as usize];
+        }
+        Transformation {transpose, rows, columns, digits}
+    }
+
+    pub fn inverse(&self) -> Transformation {
+        let mut inverse_rows = [0; 9];
+        let mut inverse_columns = [0; 9];
+        for k in 0..9 {
+            inverse_rows[self.rows[k]] = k;
+            inverse_columns[self.columns[k]] = k;
+        }
+
+        let (rows, columns) = if self.transpose {
+            (inverse_columns, inverse_rows)
+        } else {
+            (inverse_rows, inverse_columns)
+        };
+
+        let mut digits = [0; 10];
+        for d in 1..10 {
+            digits[self.digits[d] as usize] = d as Value;
+        }
+        Transformation {transpose: self.transpose, rows, columns, digits}
+    }
+}
+
+struct Minlex {
+    rows: [[Value; 9]; 9],
+    out: [Value; 81],
+    best: Option<[Value; 81]>
+}
+
+impl Minlex {
+    // Chooses the row order one row at a time, relabeling digits in order of first appearance
+    // and abandoning any ordering whose prefix is already larger than the best found so far.
+    fn search(&mut self, depth: usize, band: usize, bands_used: [bool; 3], rows_used: [bool; 9], labels: [Value; 10], next_label: Value) {
+        if depth == 9 {
+            if self.best.is_none_or(|best| self.out < best) {
+                self.best = Some(self.out);
+            }
+            return
+        }
+
+        let start = 9 * depth;
+        let bands: Vec<usize> = if depth.is_multiple_of(3) {
+            (0..3).filter(|&b| !bands_used[b]).collect()
+        } else {
+            vec!(band)
+        };
+
+        for band in bands {
+            for row in (3 * band..3 * band + 3).filter(|&r| !rows_used[r]) {
+                let mut labels = labels;
+                let mut next_label = next_label;
+                for column in 0..9 {
+                    let value = self.rows[row][column];
+                    if value != 0 && labels[value as usize] == 0 {
+                        labels[value as usize] = next_label;
+                        next_label += 1;
+                    }
+                    self.out[start + column] = labels[value as usize];
+                }
+
+                // The best can change while searching a sibling, so compare against it afresh.
+                if let Some(best) = self.best {
+                    if self.out[..start + 9] > best[..start + 9] {
+                        continue
+                    }
+                }
+
+                let mut bands_used = bands_used;
+                let mut rows_used = rows_used;
+                bands_used[band] = true;
+                rows_used[row] = true;
+                self.search(depth + 1, band, bands_used, rows_used, labels, next_label);
+            }
+        }
+    }
+}
+
+/// The minimal lexicographic (minlex) representative of the puzzle's equivalence class.
+///
+/// Blank cells read as 0 and the digits are relabeled in order of first appearance, so two
+/// puzzles are equivalent exactly when their minlex forms are equal.  Fails for grids other
+/// than 9×9.
+pub fn minlex(data: &Data) -> Result<Data, SudokuError> {
+    let mut minlex = Minlex {rows: [[0; 9]; 9], out: [0; 81], best: None};
+
+    for transpose in [false, true] {
+        for columns in band_permutations() {
+            let transformation = Transformation {
+                transpose,
+                rows: Transformation::identity().rows,
+                columns,
+                digits: Transformation::identity().digits
+            };
+            let permuted = transformation.apply(data)?;
+            for (row, values) in minlex.rows.iter_mut().enumerate() {
+                for (column, value) in values.iter_mut().enumerate() {
+                    *value = permuted[9 * row + column].unwrap_or(0);
+                }
+            }
+            minlex.search(0, 0, [false; 3], [false; 9], [0; 10], 1);
+        }
+    }
+
+    let mut out = Data::empty();
+    for (item, &value) in out.iter_mut().zip(minlex.best.unwrap().iter()) {
+        if value != 0 {
+            *item = Some(value);
+        }
+    }
+    Ok(out)
+}
+
+/// True when one puzzle can be transformed into the other by a validity preserving transformation.
+///
+/// Only 9×9 grids are compared; any other grid is equivalent to nothing.
+pub fn are_equivalent(a: &Data, b: &Data) -> bool {
+    matches!((minlex(a), minlex(b)), (Ok(a), Ok(b)) if a == b)
+}
+
+/// Rotates the grid clockwise by the given number of quarter turns.
+///
+/// Like the other symmetry operations below, this works on classic 9×9 grids only, and fails
+/// with `SudokuError::InvalidGeometry` for any other size, for jigsaws, extra units and
+/// constraints, whose rules the moved cells would no longer match.
+pub fn rotate(data: &Data, quarter_turns: usize) -> Result<Data, SudokuError> {
+    Transformation::rotation(quarter_turns).apply(data)
+}
+
+/// Mirrors the grid across an axis.
+pub fn reflect(data: &Data, axis: Axis) -> Result<Data, SudokuError> {
+    Transformation::reflection(axis).apply(data)
+}
+
+/// Swaps rows for columns, the reflection across the main diagonal.
+pub fn transpose(data: &Data) -> Result<Data, SudokuError> {
+    Transformation::transposition().apply(data)
+}
+
+/// Replaces each digit `d` with `digits[d]`, failing unless `digits` is a permutation.
+pub fn relabel(data: &Data, digits: [Value; 10]) -> Result<Data, SudokuError> {
+    Transformation::relabeling(digits).and_then(|t| t.apply(data))
+}
+
+/// Swaps two bands of three rows, counted from the top.
+pub fn swap_bands(data: &Data, a: Index, b: Index) -> Result<Data, SudokuError> {
+    Transformation::band_swap(a, b).and_then(|t| t.apply(data))
+}
+
+/// Swaps two stacks of three columns, counted from the left.
+pub fn swap_stacks(data: &Data, a: Index, b: Index) -> Result<Data, SudokuError> {
+    Transformation::stack_swap(a, b).and_then(|t| t.apply(data))
+}
+
+/// Reorders the three rows within a band, so that row `n` of the band comes from row `order[n]`.
+pub fn permute_rows(data: &Data, band: Index, order: [Index; 3]) -> Result<Data, SudokuError> {
+    Transformation::row_permutation(band, order).and_then(|t| t.apply(data))
+}
+
+/// Reorders the three columns within a stack, as `permute_rows` does for rows.
+pub fn permute_columns(data: &Data, stack: Index, order: [Index; 3]) -> Result<Data, SudokuError> {
+    Transformation::column_permutation(stack, order).and_then(|t| t.apply(data))
+}
+
+/// A disguised copy of the puzzle, with the same solving path, chosen reproducibly by `seed`.
+pub fn random_equivalent(data: &Data, seed: u64) -> Result<Data, SudokuError> {
+    Transformation::random(seed).apply(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::parse_from_file;
+
+    #[test]
+    fn test_positional_count() {
+        assert_eq!(Transformation::positional().count(), 3_359_232);
+    }
+
+    #[test]
+    fn test_compose_and_inverse() {
+        let data = parse_from_file("puzzles/puzzle_3.txt").unwrap();
+        let a = Transformation::new(
+            true, [2, 0, 1, 6, 7, 8, 4, 3, 5], [3, 5, 4, 0, 1, 2, 8, 6, 7], [0, 3, 1, 2, 5, 4, 9, 8, 7, 6]
+        ).unwrap();
+        let b = Transformation::new(
+            false, [8, 7, 6, 0, 2, 1, 3, 4, 5], [1, 0, 2, 7, 6, 8, 5, 3, 4], [0, 2, 3, 4, 5, 6, 7, 8, 9, 1]
+        ).unwrap();
+
+        assert_eq!(a.then(&b).apply(&data).unwrap(), b.apply(&a.apply(&data).unwrap()).unwrap());
+        assert_eq!(a.inverse().apply(&a.apply(&data).unwrap()).unwrap(), data);
+        assert!(Transformation::new(false, [0, 1, 3, 2, 4, 5, 6, 7, 8], Transformation::identity().columns, Transformation::identity().digits).is_err());
+    }
+
+    #[test]
+    fn test_operations() {
+        let data = parse_from_file("puzzles/puzzle_1.txt").unwrap();
+        let rotated = rotate(&data, 1).unwrap();
+
+        assert_eq!(rotated[8], data[0]);
+        assert_eq!(rotated[80], data[8]);
+        assert_eq!(rotate(&rotated, 3).unwrap(), data);
+        assert_eq!(reflect(&data, Axis::Vertical).unwrap()[8], data[0]);
+        assert_eq!(reflect(&data, Axis::AntiDiagonal).unwrap()[80], data[0]);
+        assert_eq!(transpose(&data).unwrap()[9 * 4], data[4]);
+        assert_eq!(swap_bands(&data, 0, 2).unwrap()[54], data[0]);
+        assert_eq!(permute_columns(&data, 0, [2, 0, 1]).unwrap()[0], data[2]);
+        assert!(permute_rows(&data, 3, [0, 1, 2]).is_err());
+        assert!(relabel(&data, [0, 1, 1, 3, 4, 5, 6, 7, 8, 9]).is_err());
+        assert!(rotate(&"1.3..2..3..2.4..".parse().unwrap(), 1).is_err());
+        let diagonals = Data::with_geometry(std::sync::Arc::new(crate::Geometry::new(3, 3).unwrap().with_diagonals()));
+        assert!(matches!(transpose(&diagonals), Err(SudokuError::InvalidGeometry(_))));
+    }
+
+    #[test]
+    fn test_random_equivalent() {
+        let data = parse_from_file("puzzles/puzzle_2.txt").unwrap();
+
+        assert_eq!(random_equivalent(&data, 7).unwrap(), random_equivalent(&data, 7).unwrap());
+        assert_ne!(random_equivalent(&data, 7).unwrap(), random_equivalent(&data, 8).unwrap());
+        assert!(are_equivalent(&data, &random_equivalent(&data, 7).unwrap()));
+    }
+
+    #[test]
+    fn test_equivalent() {
+        let data = parse_from_file("puzzles/puzzle_2.txt").unwrap();
+        let other = parse_from_file("puzzles/puzzle_3.txt").unwrap();
+        let transformation = Transformation::new(
+            true, [5, 4, 3, 0, 1, 2, 7, 8, 6], [6, 7, 8, 2, 0, 1, 4, 3, 5], [0, 9, 8, 7, 6, 5, 4, 3, 2, 1]
+        ).unwrap();
+        let disguised = transformation.apply(&data).unwrap();
+
+        assert_eq!(minlex(&data).unwrap(), minlex(&disguised).unwrap());
+        assert!(are_equivalent(&data, &disguised));
+        assert!(!are_equivalent(&data, &other));
+    }
+}
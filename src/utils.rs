@@ -1,68 +1,449 @@
-use std::io::prelude::*;
-use std::io::BufReader;
-use std::fs::File;
-use std::error::Error;
+use std::fs;
+use std::sync::Arc;
 
-use crate::{Data, Index};
+use crate::{Cage, Clue, Constraint, Data, Dot, Geometry, Index, Inequality, Line, Negative, Parity, SudokuError, Unit};
+use crate::clues::{ClueKind, parse_clue, write_clues};
+use crate::comparison::{parse_comparison, write_comparisons};
+use crate::dots::{parse_dot_rule, write_dots};
+use crate::killer::{parse_cage, write_cages};
+use crate::lines::{LineKind, parse_line_rule, write_lines};
 
-pub fn parse_from_file(filename: &str) -> Result<Data, Box<dyn Error>>{
-    let f = File::open(filename).expect("Unable to open file");
+/// Reads a puzzle file, either as `index,value` lines or in the one-line format.
+pub fn parse_from_file(filename: &str) -> Result<Data, SudokuError> {
+    let content = fs::read_to_string(filename)?;
+    parse_from_str(&content)
+}
+
+/// Parses a puzzle, picking the `index,value` line format when any line contains a comma
+/// and the one-line format otherwise.
+///
+/// `index,value` files are 9×9 unless they start with a `size <width>x<height>` line giving
+/// the box dimensions, such as `size 3x2` for 6×6 grids.
+///
+/// Either format may be mixed with lines declaring the rules of variants: `cage <sum> <index>
+/// <index> ...` for killer cages, and the lines read by `dots::parse_dot_rule`,
+/// `comparison::parse_comparison`, `lines::parse_line_rule` and `clues::parse_clue`.  The grid
+/// itself may then be left out when there are no givens.
+///
+/// A `layout samurai`, `layout twin` or `layout butterfly` line reads the puzzle onto the
+/// overlapping grids of `Geometry::layout`.  The one-line format then lists the cells of the
+/// whole layout in reading order, which may be drawn as the picture with gaps left blank.
+pub fn parse_from_str(content: &str) -> Result<Data, SudokuError> {
+    parse_puzzle(content, None)
+}
 
-    let reader = BufReader::new(f);
+/// Parses a puzzle in either format onto a given geometry, such as a jigsaw layout from
+/// `regions::read_regions`.
+pub fn parse_with_geometry(content: &str, geometry: Arc<Geometry>) -> Result<Data, SudokuError> {
+    parse_puzzle(content, Some(geometry))
+}
 
-    let mut data = [None; 81];
+// The first words of the lines declaring the rules of variants, rather than the grid.
+const RULES: [&str; 17] = [
+    "cage", "white", "black", "x", "v", "negative", "non-consecutive", "odd", "even", "greater",
+    "thermo", "arrow", "whisper", "renban", "palindrome", "sandwich", "little"
+];
 
-    for (n, line) in reader.lines().enumerate() {
-        let line = line.expect(format!("Error reading line: {}", n).as_ref());
-        //println!("{:?}", line);
-        let mut vals = line.split(",");
+fn is_rule(line: &str) -> bool {
+    line.split_whitespace().next().is_some_and(|word| RULES.contains(&word))
+}
 
-        let index = vals.next().unwrap().parse::<usize>()?;
-        let value = vals.next().expect("Error splitting line value").parse::<u8>().expect("Unable to parse value");
+fn is_layout(line: &str) -> bool {
+    line.split_whitespace().next() == Some("layout")
+}
 
-        data[index] = Some(value);
+// Reads a `layout <name>` line into the overlapping grids of that name.
+fn parse_layout(line: &str, line_number: usize) -> Result<Geometry, SudokuError> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    fields.get(1).filter(|_| fields.len() == 2).and_then(|name| Geometry::layout(name))
+        .ok_or_else(|| SudokuError::Parse {
+            line: line_number, column: 1, message: "expected `layout samurai`, `layout twin` or `layout butterfly`".into()
+        })
+}
+
+// Adds the rule declared on a line to the geometry.
+fn apply_rule(geometry: Geometry, line: &str, line_number: usize) -> Result<Geometry, SudokuError> {
+    match line.split_whitespace().next() {
+        Some("cage") => {
+            let cage = parse_cage(line, line_number, geometry.size())?;
+            geometry.with_cage(cage)
+        },
+        Some(word) if LineKind::from_name(word).is_some() => parse_line_rule(geometry, line, line_number),
+        Some("sandwich" | "little") => parse_clue(geometry, line, line_number),
+        Some("odd" | "even" | "greater") => parse_comparison(geometry, line, line_number),
+        _ => parse_dot_rule(geometry, line, line_number)
+    }
+}
+
+/// Adds rule lines, as written by `write_rules`, to a geometry.  Blank lines are skipped, and
+/// any other line which is not a rule is an error.
+pub fn parse_rules(geometry: Geometry, content: &str) -> Result<Geometry, SudokuError> {
+    content.lines().enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .try_fold(geometry, |geometry, (n, line)| apply_rule(geometry, line, n + 1))
+}
+
+/// Writes the cages, dots, lines, clues and comparisons of a geometry as rule lines, the
+/// counterpart of `parse_rules`.  Constraints of any other type are left out.
+pub fn write_rules(geometry: &Geometry) -> String {
+    [write_cages(geometry), write_dots(geometry), write_lines(geometry), write_clues(geometry), write_comparisons(geometry)].concat()
+}
+
+// True when `write_rules` writes out a constraint of this type.
+#[cfg_attr(not(feature = "serde"), allow(dead_code))]
+pub(crate) fn is_written_rule(constraint: &dyn Constraint) -> bool {
+    constraint.downcast_ref::<Cage>().is_some() || constraint.downcast_ref::<Dot>().is_some()
+        || constraint.downcast_ref::<Negative>().is_some() || constraint.downcast_ref::<Line>().is_some()
+        || constraint.downcast_ref::<Clue>().is_some() || constraint.downcast_ref::<Parity>().is_some()
+        || constraint.downcast_ref::<Inequality>().is_some()
+}
+
+// Reads the grid with the rule lines blanked out, keeping line numbers, then adds the rules.
+fn parse_puzzle(content: &str, geometry: Option<Arc<Geometry>>) -> Result<Data, SudokuError> {
+    let mut geometry = geometry;
+    let layout = content.lines().enumerate().find(|(_, line)| is_layout(line));
+    if let Some((n, line)) = layout {
+        let named = parse_layout(line, n + 1)?;
+        match &geometry {
+            Some(geometry) if geometry.grids() != named.grids() => {
+                return Err(SudokuError::InvalidGeometry("the layout does not match the geometry".into()));
+            },
+            Some(_) => {},
+            None => geometry = Some(Arc::new(named))
+        }
+    }
+
+    let has_rules = content.lines().any(is_rule);
+    let grid = content.lines()
+        .map(|line| if is_rule(line) || is_layout(line) { "" } else { line })
+        .collect::<Vec<&str>>().join("\n");
+
+    let index_values = grid.lines().any(|line| line.contains(',') || line.trim_start().starts_with("size"));
+    let data = if index_values || grid.trim().is_empty() && (has_rules || layout.is_some()) {
+        parse_index_values(&grid, geometry)?
+    } else {
+        match geometry {
+            Some(geometry) => Data::parse_with(geometry, &grid)?,
+            None => grid.parse()?
+        }
+    };
+    if !has_rules {
+        return Ok(data);
+    }
+
+    let mut geometry = data.geometry().as_ref().clone();
+    for (n, line) in content.lines().enumerate().filter(|(_, line)| is_rule(line)) {
+        geometry = apply_rule(geometry, line, n + 1)?;
+    }
+    let mut ruled = Data::with_geometry(Arc::new(geometry));
+    ruled.copy_from_slice(&data);
+    Ok(ruled)
+}
+
+fn parse_index_values(content: &str, geometry: Option<Arc<Geometry>>) -> Result<Data, SudokuError> {
+    let mut lines = content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()).peekable();
+
+    let size = match lines.peek() {
+        Some(&(n, line)) if line.trim_start().starts_with("size") => {
+            lines.next();
+            Some(parse_size(line, n + 1)?)
+        },
+        _ => None
+    };
+    let geometry = match (geometry, size) {
+        (Some(geometry), Some(size)) if geometry.size() != size.size() => {
+            return Err(SudokuError::InvalidGeometry(format!("expected a {} by {} grid", geometry.size(), geometry.size())));
+        },
+        (Some(geometry), _) => geometry,
+        (None, Some(size)) => Arc::new(size),
+        (None, None) => Geometry::classic()
+    };
+    let mut data = Data::with_geometry(geometry);
+
+    for (n, line) in lines {
+        let (index, value) = parse_line(line, n + 1)?;
+
+        if index >= data.len() {
+            return Err(SudokuError::IndexOutOfRange(index));
+        }
+        if value == 0 || value > data.geometry().size() {
+            return Err(SudokuError::DigitOutOfRange(value));
+        }
+        if data[index].is_some() {
+            return Err(SudokuError::DuplicateEntry(index));
+        }
+
+        data[index] = Some(value as u8);
     }
 
     Ok(data)
 }
 
+// Reads a `size <width>x<height>` line into the geometry with boxes of that shape.
+fn parse_size(line: &str, line_number: usize) -> Result<Geometry, SudokuError> {
+    let dimensions = line.trim().strip_prefix("size").unwrap().trim();
+    let (width, height) = dimensions.split_once('x')
+        .and_then(|(w, h)| Some((w.trim().parse::<usize>().ok()?, h.trim().parse::<usize>().ok()?)))
+        .ok_or_else(|| SudokuError::Parse {
+            line: line_number, column: 1, message: "expected `size <width>x<height>`".into()
+        })?;
+    Geometry::new(width, height)
+}
+
+// Splits an `index,value` line, reporting the 1-based column of any field that fails to parse.
+fn parse_line(line: &str, line_number: usize) -> Result<(usize, usize), SudokuError> {
+    let parse_error = |column: usize, message: &str| SudokuError::Parse {
+        line: line_number, column, message: message.into()
+    };
+
+    let (index, value) = line.split_once(',')
+        .ok_or_else(|| parse_error(1, "expected `index,value`"))?;
+
+    let index = index.trim().parse::<usize>()
+        .map_err(|_| parse_error(1, "unable to parse index"))?;
+    let value = value.trim().parse::<usize>()
+        .map_err(|_| parse_error(line.find(',').unwrap() + 2, "unable to parse value"))?;
+
+    Ok((index, value))
+}
+
 pub fn print_puzzle_(data: Data) {
-    
+    let geometry = data.geometry();
+
     for (idx, item) in data.iter().enumerate() {
         match item {
-            Some(value) => print!(" {} ", value.to_string()),
+            Some(value) => print!(" {} ", geometry.digit_char(*value)),
             None => print!("   ")
         };
-        if idx % 9 == 0 && idx != 0{
-            print!("\n");
+        if idx % geometry.size() == 0 && idx != 0{
+            println!();
         }
     }
 }
 
 pub fn print_puzzle(data: &Data ) {
-    let horizontal_line ="+ - + - + - + - + - + - + - + - + - +";
-    
-    for row_index in 0..9 {
-        if (row_index % 3 == 0) && (row_index != 0) {
-            println!("{}", horizontal_line);
+    print!("{}", write_puzzle(data));
+}
+
+/// The grid as text, as shown by `print_puzzle`.
+///
+/// Dots, XV marks and greater-than signs sit between the cells they join.  Sandwich clues go
+/// above the columns and after the rows, and Little Killer clues below.  Overlapping grids are
+/// drawn on their shared canvas, with the gaps between them left blank.
+pub fn write_puzzle(data: &Data) -> String {
+    let geometry = data.geometry();
+    let (width, height) = (geometry.width(), geometry.height());
+    // Each cell is written 4 characters after the one before, starting from the third
+    let column_at = |column: usize| 2 + 4 * column;
+    let position = |idx: Index| {
+        let position = geometry.position_from_index(idx);
+        (position.row(), position.column())
+    };
+
+    let sandwiches = |unit: Unit, at: usize| -> Vec<usize> {
+        geometry.constraints_of::<Clue>().filter_map(|clue| match clue.kind() {
+            ClueKind::Sandwich {unit: clued, sum, ..} if *clued == unit => {
+                let (row, column) = position(clue.cells()[0]);
+                Some(*sum).filter(|_| at == if unit == Unit::Row { row } else { column })
+            },
+            _ => None
+        }).collect()
+    };
+    // The marks between neighbours, each with the top or left cell first; signs point at the
+    // lesser digit
+    let signs = geometry.constraints_of::<Inequality>().map(|inequality| {
+        let (greater, lesser) = (inequality.greater(), inequality.lesser());
+        let across = position(greater).0 == position(lesser).0;
+        match (greater < lesser, across) {
+            (true, true) => (greater, lesser, '>'),
+            (false, true) => (lesser, greater, '<'),
+            (true, false) => (greater, lesser, 'v'),
+            (false, false) => (lesser, greater, '^')
+        }
+    });
+    let marks: Vec<(Index, Index, char)> = geometry.constraints_of::<Dot>()
+        .map(|dot| (dot.first(), dot.second(), dot.kind().symbol()))
+        .chain(signs)
+        .collect();
+
+    let mut out = String::new();
+    if (0..width).any(|column| !sandwiches(Unit::Column, column).is_empty()) {
+        let clues: String = (0..width).map(|column| {
+            format!("{:<4}", sandwiches(Unit::Column, column).first().map_or(String::new(), |sum| sum.to_string()))
+        }).collect();
+        out += &format!("  {}\n", clues.trim_end());
+    }
+
+    for row_index in 0..height {
+        if row_index != 0 {
+            let border = (row_index % geometry.box_height() == 0) && !geometry.is_jigsaw();
+            let mut line: Vec<char> = vec!(' '; column_at(width));
+            if border {
+                let edges = (0..width).filter(|&column| {
+                    geometry.index_at(row_index - 1, column).or(geometry.index_at(row_index, column)).is_some()
+                });
+                for column in edges {
+                    line[column_at(column) - 2] = '+';
+                    line[column_at(column)] = '-';
+                    line[column_at(column) + 2] = '+';
+                }
+            }
+            let mut marked = false;
+            for (first, second, symbol) in marks.iter() {
+                let ((first_row, column), (second_row, second_column)) = (position(*first), position(*second));
+                if second_row == row_index && first_row + 1 == row_index && column == second_column {
+                    line[column_at(column)] = *symbol;
+                    marked = true;
+                }
+            }
+            if border || marked {
+                out += &format!("{}\n", line.into_iter().collect::<String>().trim_end());
+            }
+        }
+
+        let mut line: Vec<char> = write_line(data, row_index).chars().collect();
+        for (first, second, symbol) in marks.iter() {
+            let ((row, column), (second_row, _)) = (position(*first), position(*second));
+            if row == row_index && second_row == row_index {
+                line[column_at(column) + 2] = *symbol;
+            }
+        }
+        let mut line: String = line.into_iter().collect();
+        for sum in sandwiches(Unit::Row, row_index) {
+            line += &format!("  {}", sum);
+        }
+        out += &format!("{}\n", line);
+    }
+
+    for clue in geometry.constraints_of::<Clue>() {
+        if let ClueKind::LittleKiller {start, direction, sum} = clue.kind() {
+            out += &format!("  {} {} from cell {}\n", direction.arrow(), sum, start);
+        }
+    }
+    out
+}
+
+// A row of the canvas, with `¦` between the boxes of a grid and a trailing space after the
+// last cell.
+fn write_line(data: &Data, row_index: Index) -> String {
+    let geometry = data.geometry();
+    let cells: Vec<Option<Index>> = (0..geometry.width()).map(|column| geometry.index_at(row_index, column)).collect();
+    let last = cells.iter().rposition(|cell| cell.is_some()).unwrap_or(0);
+
+    let mut line = vec!(' '; 4 * last + 4);
+    for (column, cell) in cells.iter().enumerate() {
+        let Some(idx) = cell else {
+            continue
+        };
+        if let Some(value) = data[*idx] {
+            line[2 + 4 * column] = geometry.digit_char(value);
+        }
+        // Jigsaw regions don't line up with the box separators, so those are left out
+        let boundary = (column + 1) % geometry.box_width() == 0 && !geometry.is_jigsaw();
+        if boundary && column < last && cells[column + 1].is_some() {
+            line[4 + 4 * column] = '¦';
         }
-        print_line(data, row_index)
     }
-    // println!("{}", horizontal_line);
+    line.into_iter().collect()
 }
 
-fn print_line(data: &Data, row_index: Index) {
-    let no_value = " ";
-    let row_vals = &data[9*row_index..9*row_index+9];
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DotKind, ParityKind};
+
+    #[test]
+    fn test_index_values_with_size() {
+        let data = parse_from_str("size 3x2\n0,6\n35,1\n").unwrap();
+
+        assert_eq!(data.geometry().size(), 6);
+        assert_eq!((data[0], data[35]), (Some(6), Some(1)));
+        assert!(matches!(parse_from_str("size 3x2\n36,1\n"), Err(SudokuError::IndexOutOfRange(36))));
+        assert!(matches!(parse_from_str("size 3x2\n0,7\n"), Err(SudokuError::DigitOutOfRange(7))));
+        assert!(matches!(parse_from_str("size 3\n0,1\n"), Err(SudokuError::Parse {line: 1, ..})));
+        assert_eq!(parse_from_str("0,9\n").unwrap().len(), 81);
+    }
+
+    #[test]
+    fn test_cages() {
+        let data = parse_from_str("cage 3 0 1\n0,1\ncage 17 9 10\n").unwrap();
+        assert_eq!(data.geometry().constraints_of::<Cage>().count(), 2);
+        assert_eq!((data[0], data[1]), (Some(1), None));
+        assert_eq!(parse_from_str("size 3x2\ncage 11 0 1 2\n").unwrap().geometry().size(), 6);
+        assert_eq!(parse_from_str("cage 3 0 1\n").unwrap().count(), 0);
+        assert!(matches!(parse_from_str("cage 3 0 1\ncage 4 1 2\n"), Err(SudokuError::InvalidGeometry(_))));
+        assert!(matches!(parse_from_str("0,1\ncage 3 0 z\n"), Err(SudokuError::Parse {line: 2, ..})));
+    }
+
+    #[test]
+    fn test_dots() {
+        let content = format!("white 0 1\nnon-consecutive\n{}", "1".to_string() + &".".repeat(80));
+        let data = parse_from_str(&content).unwrap();
+        assert_eq!(data[0], Some(1));
+        assert_eq!(data.geometry().constraints_of::<Dot>().count(), 1);
+        let negative: Vec<DotKind> = data.geometry().constraints_of::<Negative>().map(|negative| negative.kind()).collect();
+        assert_eq!(negative, vec!(DotKind::White));
+        assert!(matches!(parse_from_str("black 0 10\n"), Err(SudokuError::InvalidGeometry(_))));
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let data = parse_from_str("odd 0 2\ngreater 1 0\ngreater 1 10\ngreater 18 9\n0,3\n").unwrap();
+        let shaded = data.geometry().constraints_of::<Parity>().find(|parity| parity.cell() == 2);
+        assert_eq!(shaded.map(|parity| parity.kind()), Some(ParityKind::Odd));
+        assert_eq!(data.geometry().constraints_of::<Inequality>().count(), 3);
+
+        let lines: Vec<String> = write_puzzle(&data).lines().map(String::from).collect();
+        assert_eq!(lines[0], "  3 <       ¦           ¦           ");
+        assert_eq!(lines[1], "      v");
+        assert_eq!(lines[3], "  ^");
+        assert!(matches!(parse_from_str("greater 0 2\n"), Err(SudokuError::InvalidGeometry(_))));
+    }
+
+    #[test]
+    fn test_lines() {
+        let data = parse_from_str("thermo 0 1 2\narrow 40 30\n0,1\n").unwrap();
+        assert_eq!(data[0], Some(1));
+        assert_eq!(data.geometry().constraints_of::<Line>().count(), 2);
+        assert!(matches!(parse_from_str("0,1\nrenban 0 20\n"), Err(SudokuError::InvalidGeometry(_))));
+    }
+
+    #[test]
+    fn test_rules_round_trip() {
+        let rules = "cage 3 0 1\nnegative v\nwhite 1 2\nthermo 3 4 5\nlittle 15 8 down-left\nodd 9\ngreater 10 11\n";
+        let geometry = parse_rules(Geometry::new(3, 3).unwrap(), rules).unwrap();
+        assert_eq!(geometry.constraints().len(), 7);
+        assert_eq!(write_rules(&geometry), rules);
+        assert!(matches!(parse_rules(geometry, "\n12345\n"), Err(SudokuError::Parse {line: 2, ..})));
+    }
 
-    let row = row_vals.chunks(3)
-        .map(|chunk| 
-            chunk.iter().map(|item| 
-                match item {
-                    Some(val) => val.to_string(),
-                    None => no_value.into()
-                }).collect::<Vec<String>>().join("   ")
-    ).collect::<Vec<String>>().join(" ¦ ");
-    println!("  {} ", row);
+    #[test]
+    fn test_write_puzzle() {
+        let content = "sandwich column 1 12\nsandwich row 0 0\nx 0 1\nv 3 12\nlittle 15 8 down-left\n0,5\n";
+        let lines: Vec<String> = write_puzzle(&parse_from_str(content).unwrap()).lines().map(String::from).collect();
 
-}
\ No newline at end of file
+        assert_eq!(lines[0], "      12");
+        assert_eq!(lines[1], "  5 X       ¦           ¦             0");
+        assert_eq!(lines[2], "              V");
+        assert_eq!(lines[5], "+ - + - + - + - + - + - + - + - + - +");
+        assert_eq!(lines.last().unwrap(), "  ↙ 15 from cell 8");
+        assert_eq!(lines.len(), 1 + 9 + 1 + 2 + 1);
+    }
+
+    #[test]
+    fn test_layout() {
+        let data = parse_from_str("layout twin\n0,5\n152,9\n").unwrap();
+        assert_eq!(data.len(), 153);
+        assert_eq!(data.geometry().grids(), &[(0, 0), (6, 6)]);
+
+        let lines: Vec<String> = write_puzzle(&data).lines().map(String::from).collect();
+        assert_eq!(lines.len(), 15 + 4);
+        assert_eq!(lines[0], "  5         ¦           ¦           ");
+        assert_eq!(lines[7], "+ - + - + - + - + - + - + - + - + - + - + - + - + - + - + - +");
+        assert_eq!(lines[15], "                        + - + - + - + - + - + - + - + - + - +");
+        assert_eq!(lines[18], "                                    ¦           ¦         9 ");
+        assert_eq!(parse_from_str(&format!("layout twin\n{}", ".".repeat(153))).unwrap().len(), 153);
+        assert!(matches!(parse_from_str("layout twin\n.........\n"), Err(SudokuError::Parse {..})));
+        assert!(matches!(parse_from_str("layout pentagon\n0,5\n"), Err(SudokuError::Parse {line: 1, ..})));
+    }
+}
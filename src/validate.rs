@@ -0,0 +1,150 @@
+use crate::{Data, Index, Value, Relation, Unit};
+
+/// A reason a grid cannot be completed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum Violation {
+    /// Two cells of the same unit hold the same digit
+    Conflict { first: Index, second: Index, value: Value, unit: Unit, unit_index: Index },
+    /// An empty cell whose peers already use every digit
+    NoCandidates(Index),
+    /// Two related cells, such as a knight's move apart, hold the same digit
+    Related { first: Index, second: Index, value: Value, relation: Relation },
+    /// A cell holding a value outside 1..=size
+    DigitOutOfRange(Index),
+    /// A constraint, by position in `Geometry::constraints`, which the digits break, such as a
+    /// killer cage whose digits repeat or cannot make its sum
+    Constraint(Index)
+}
+
+/// Checks the givens of a grid against all of its rules.
+///
+/// Every conflicting pair of cells is reported, along with every broken constraint, such as a
+/// killer cage or a dot, and every empty cell left without candidates.  An empty list means no
+/// problems were found.
+pub fn validate(data: &Data) -> Vec<Violation> {
+    let geometry = data.geometry();
+    let mut violations = vec!();
+
+    for (idx, item) in data.iter().enumerate() {
+        if let Some(value) = item {
+            if !geometry.values().contains(value) {
+                violations.push(Violation::DigitOutOfRange(idx));
+            }
+        }
+    }
+
+    for (unit, unit_index, idxs) in geometry.units() {
+        for (n, &first) in idxs.iter().enumerate() {
+            for &second in idxs[n + 1..].iter() {
+                if let (Some(value), Some(other)) = (data[first], data[second]) {
+                    if value == other {
+                        violations.push(Violation::Conflict {first, second, value, unit, unit_index});
+                    }
+                }
+            }
+        }
+    }
+
+    for &relation in geometry.relations() {
+        for first in 0..data.len() {
+            let targets = relation.offsets().iter().filter_map(|&step| geometry.step(first, step));
+            for second in targets.filter(|&second| second > first) {
+                if let (Some(value), Some(other)) = (data[first], data[second]) {
+                    if value == other {
+                        violations.push(Violation::Related {first, second, value, relation});
+                    }
+                }
+            }
+        }
+    }
+
+    for (n, constraint) in geometry.constraints().iter().enumerate() {
+        if !constraint.is_consistent(data) {
+            violations.push(Violation::Constraint(n));
+        }
+    }
+
+    for idx in (0..data.len()).filter(|&i| data[i].is_none()) {
+        let mut seen = vec!(false; geometry.size() + 1);
+        for &oidx in geometry.around(idx) {
+            if let Some(value) = data[oidx] {
+                if (value as usize) < seen.len() {
+                    seen[value as usize] = true;
+                }
+            }
+        }
+        if seen[1..].iter().all(|&s| s) {
+            violations.push(Violation::NoCandidates(idx));
+        }
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{Cage, Dot, DotKind, Geometry, Inequality, Line, LineKind, Parity};
+    use crate::comparison::ParityKind;
+
+    #[test]
+    fn test_validate() {
+        let mut data = Data::empty();
+        assert!(validate(&data).is_empty());
+
+        data[0] = Some(5);
+        data[10] = Some(5);
+        data[80] = Some(12);
+        assert_eq!(validate(&data), vec!(
+            Violation::DigitOutOfRange(80),
+            Violation::Conflict {first: 0, second: 10, value: 5, unit: Unit::Subsquare, unit_index: 0}
+        ));
+
+        let mut data = Data::empty();
+        for (n, idx) in [1, 2, 3, 4, 5, 6, 7, 8, 9].iter().enumerate() {
+            data[*idx] = Some(n as Value + 1);
+        }
+        assert_eq!(validate(&data), vec!(Violation::NoCandidates(0)));
+
+        let geometry = Geometry::new(3, 3).unwrap().with_cage(Cage::new(vec!(0, 1, 2), 10, 9).unwrap()).unwrap();
+        let mut data = Data::with_geometry(Arc::new(geometry));
+        data[0] = Some(4);
+        assert!(validate(&data).is_empty());
+        data[1] = Some(6);
+        assert_eq!(validate(&data), vec!(Violation::Constraint(0)));
+
+        let mut data = Data::with_geometry(Arc::new(Geometry::new(3, 3).unwrap().with_relation(Relation::AntiKnight)));
+        data[40] = Some(3);
+        data[24] = Some(3);
+        assert!(validate(&data).is_empty());
+        data[23] = Some(3);
+        assert_eq!(validate(&data), vec!(
+            Violation::Conflict {first: 23, second: 24, value: 3, unit: Unit::Row, unit_index: 2},
+            Violation::Related {first: 23, second: 40, value: 3, relation: Relation::AntiKnight}
+        ));
+
+        let geometry = Geometry::new(3, 3).unwrap().with_dot(Dot::new(0, 1, DotKind::Black)).unwrap().with_non_consecutive();
+        let mut data = Data::with_geometry(Arc::new(geometry));
+        data[0] = Some(3);
+        data[1] = Some(6);
+        data[9] = Some(4);
+        assert_eq!(validate(&data), vec!(Violation::Constraint(1)));
+
+        let geometry = Geometry::new(3, 3).unwrap().with_line(Line::new(LineKind::Thermometer, vec!(0, 1, 2))).unwrap();
+        let mut data = Data::with_geometry(Arc::new(geometry));
+        data[1] = Some(1);
+        assert_eq!(validate(&data), vec!(Violation::Constraint(0)));
+
+        let geometry = Geometry::new(3, 3).unwrap()
+            .with_parity(Parity::new(0, ParityKind::Odd)).unwrap()
+            .with_inequality(Inequality::new(2, 1)).unwrap();
+        let mut data = Data::with_geometry(Arc::new(geometry));
+        data[0] = Some(4);
+        data[1] = Some(6);
+        data[2] = Some(5);
+        assert_eq!(validate(&data), vec!(Violation::Constraint(0), Violation::Constraint(1)));
+    }
+}